@@ -0,0 +1,13 @@
+#include <inttypes.h>
+#include <stdbool.h>
+#include <stdio.h>
+
+void putfloatd(double f) { printf("%f\n", f); }
+
+void putint(int64_t i) { printf("%" PRId64 "\n", i); }
+
+void putbool(bool b) { puts(b ? "true" : "false"); }
+
+void aurora_assert_failed(const char *message) {
+    fprintf(stderr, "assertion failed: %s\n", message);
+}
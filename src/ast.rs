@@ -1,46 +1,264 @@
+use crate::{intern::Symbol, lexer::Span, typing::AuroraType};
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum BinaryOp {
+    And,
+    Or,
+    Equal,
+    NotEqual,
     LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
     Minus,
     Plus,
     Times,
+    Divide,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOp {
+    // How tightly the operator binds, operators with a higher precedence being applied first.
+    // The match is exhaustive so no operator, `==` included, can be left without one
+    pub fn precedence(&self) -> i32 {
+        match self {
+            BinaryOp::Or => 4,
+            BinaryOp::And => 6,
+            BinaryOp::LessThan
+            | BinaryOp::LessEqual
+            | BinaryOp::GreaterThan
+            | BinaryOp::GreaterEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual => 10,
+            BinaryOp::BitOr => 12,
+            BinaryOp::BitXor => 13,
+            BinaryOp::BitAnd => 14,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 16,
+            BinaryOp::Plus | BinaryOp::Minus => 20,
+            BinaryOp::Times | BinaryOp::Divide | BinaryOp::Modulo => 40,
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::LessThan
+                | BinaryOp::LessEqual
+                | BinaryOp::GreaterThan
+                | BinaryOp::GreaterEqual
+                | BinaryOp::Equal
+                | BinaryOp::NotEqual
+        )
+    }
+
+    pub fn is_logical(&self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
+
+    // Comparisons that order their operands, as opposed to testing them for equality
+    pub fn is_ordering(&self) -> bool {
+        self.is_comparison() && !matches!(self, BinaryOp::Equal | BinaryOp::NotEqual)
+    }
+
+    pub fn is_shift(&self) -> bool {
+        matches!(self, BinaryOp::ShiftLeft | BinaryOp::ShiftRight)
+    }
+}
+
+// Builtins taking two integers of the same type and called like functions:
+// `wrapping_add`, `wrapping_sub` and `wrapping_mul` wrap around whatever the overflow
+// setting, `saturating_add`, `saturating_sub` and `saturating_mul` clamp to the range of
+// the type
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Intrinsic {
+    Wrapping(BinaryOp),
+    Saturating(BinaryOp),
+}
+
+impl Intrinsic {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (behavior, op) = name.split_once('_')?;
+        let op = match op {
+            "add" => BinaryOp::Plus,
+            "sub" => BinaryOp::Minus,
+            "mul" => BinaryOp::Times,
+            _ => return None,
+        };
+        match behavior {
+            "wrapping" => Some(Intrinsic::Wrapping(op)),
+            "saturating" => Some(Intrinsic::Saturating(op)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum UnaryOp {
+    BitNot,
+    Negate,
 }
 
 // TODO: Add types to expressions
 #[derive(Debug)]
 pub enum ExprType {
     Binary(BinaryOp, Box<Expr>, Box<Expr>),
-    Call(String, Vec<Expr>),
-    Integer(i64),
+    Intrinsic(Intrinsic, Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Call(Symbol, Vec<Argument>),
+    Integer(i128),
     Float(f64),
-    Let(String, Option<Box<Expr>>),
-    Assign(String, Box<Expr>),
-    Variable(String),
+    Boolean(bool),
+    Str(String),
+    // Name, whether it's mutable and its initial value
+    Let(Symbol, bool, Option<Box<Expr>>),
+    Assign(Symbol, Box<Expr>),
+    Variable(Symbol),
+    // Array and index
+    Index(Box<Expr>, Box<Expr>),
+    // Array, index and value
+    AssignIndex(Box<Expr>, Box<Expr>, Box<Expr>),
+    AddressOf(Box<Expr>),
+    Deref(Box<Expr>),
+    // Pointer and value
+    AssignDeref(Box<Expr>, Box<Expr>),
+    // Struct and the name of the field
+    Field(Box<Expr>, String),
+    // Value of each field of a struct, in the order they're written
+    Struct(Vec<(String, Expr)>),
+    // Byte size of a type, only known once the target is
+    SizeOf(AuroraType),
+    // `value as type`, converted to the type of the expression
+    Cast(Box<Expr>),
+    // Condition and the message reported when it's false
+    Assert(Box<Expr>, Option<String>),
     Block(Vec<Expr>),
     Return(Option<Box<Expr>>),
+    IfElse(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    // Condition, body and label of the loop
+    While(Box<Expr>, Box<Expr>, Option<String>),
+    // Body, condition checked after each run of it and label of the loop
+    DoWhile(Box<Expr>, Box<Expr>, Option<String>),
+    Loop(Box<Expr>, Option<String>),
+    // Label of the loop left or continued, the innermost one when `None`
+    Break(Option<String>),
+    Continue(Option<String>),
+    // Matched value, the pattern and body of each arm and the body of the `_` arm
+    Match(Box<Expr>, Vec<(i128, Expr)>, Box<Expr>),
 }
 
 #[derive(Debug)]
 pub struct Expr {
     pub expr_type: ExprType,
-    pub type_: &'static str,
+    pub type_: AuroraType,
+    pub span: Span,
+}
+
+impl Expr {
+    // Whether every path through the expression leaves it by a `return`, `break` or
+    // `continue`
+    pub fn diverges(&self) -> bool {
+        match &self.expr_type {
+            ExprType::Return(_) | ExprType::Break(_) | ExprType::Continue(_) => true,
+            ExprType::Block(exprs) => exprs.iter().any(Expr::diverges),
+            ExprType::IfElse(_, then, Some(else_)) => then.diverges() && else_.diverges(),
+            ExprType::Match(_, arms, default) => {
+                arms.iter().all(|(_, body)| body.diverges()) && default.diverges()
+            }
+            _ => false,
+        }
+    }
+
+    // Whether this is a literal that narrows to the type, as an integer literal does to an
+    // integer type its value fits in and a float literal to an `f32`
+    pub fn narrows_to(&self, type_: &AuroraType) -> bool {
+        match self.expr_type {
+            ExprType::Integer(value) => {
+                self.type_.is_integer()
+                    && type_.is_integer()
+                    && type_.fits_literal(value, &self.type_)
+            }
+            ExprType::Float(_) => *type_ == AuroraType::F32,
+            _ => false,
+        }
+    }
+}
+
+// An argument at a call site, optionally naming the parameter it's for
+#[derive(Debug)]
+pub struct Argument {
+    pub name: Option<Symbol>,
+    pub value: Expr,
 }
 
 #[derive(Debug)]
 pub struct Function {
     pub prototype: Prototype,
     pub body: Expr,
+    // Line of the `fn` keyword
+    pub line: usize,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Parameter {
-    pub name: String,
-    pub type_: &'static str,
+    pub name: Symbol,
+    pub type_: AuroraType,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Prototype {
-    pub function_name: String,
+    pub function_name: Symbol,
     pub parameters: Vec<Parameter>,
-    pub return_type: &'static str,
+    pub return_type: AuroraType,
+    pub variadic: bool,
+}
+
+impl Prototype {
+    pub fn param_types(&self) -> Vec<AuroraType> {
+        self.parameters
+            .iter()
+            .map(|parameter| parameter.type_.clone())
+            .collect()
+    }
+
+    // Symbol of an overloaded function, telling it apart by its parameter types
+    pub fn mangled_name(&self) -> Symbol {
+        let types: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.type_.to_string())
+            .collect();
+        Symbol::intern(&format!("{}({})", self.function_name, types.join(",")))
+    }
+
+    // Parameter the argument at this position is given to, none for a variadic argument
+    pub fn parameter(&self, position: usize, arg: &Argument) -> Option<&Parameter> {
+        match &arg.name {
+            Some(name) => self.parameters.iter().find(|param| param.name == *name),
+            None => self.parameters.get(position),
+        }
+    }
+
+    // Whether a call with these arguments could be to this function, which is how
+    // overloads are told apart. With `narrowing`, literals may narrow to their parameter
+    pub fn accepts(&self, args: &[Argument], narrowing: bool) -> bool {
+        let count = self.parameters.len();
+        if args.len() < count || (args.len() > count && !self.variadic) {
+            return false;
+        }
+        args.iter().enumerate().all(|(i, arg)| {
+            match self.parameter(i, arg) {
+                Some(parameter) => {
+                    parameter.type_ == arg.value.type_
+                        || (narrowing && arg.value.narrows_to(&parameter.type_))
+                }
+                // Variadic arguments take any type
+                None => arg.name.is_none(),
+            }
+        })
+    }
 }
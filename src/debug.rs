@@ -0,0 +1,281 @@
+use std::{collections::HashMap, env};
+
+use cranelift::codegen::CompiledCode;
+use cranelift_module::FuncId;
+use cranelift_object::ObjectProduct;
+use gimli::{
+    write::{
+        Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Range, RangeList,
+        Sections, Writer,
+    },
+    Encoding, Format, LineEncoding, RunTimeEndian, SectionId,
+};
+use object::{write::Relocation, RelocationEncoding, RelocationFlags, RelocationKind, SectionKind};
+
+use crate::error::{Error, Result};
+
+// Line information of the compiled functions, written as DWARF sections once the module
+// is finished
+pub struct DebugInfo {
+    source_name: String,
+    functions: Vec<FunctionInfo>,
+}
+
+struct FunctionInfo {
+    id: FuncId,
+    name: String,
+    line: u64,
+    size: u64,
+    // Code offset and the source line it starts
+    rows: Vec<(u64, u64)>,
+}
+
+impl DebugInfo {
+    pub fn new(source_name: &str) -> Self {
+        Self {
+            source_name: source_name.to_string(),
+            functions: vec![],
+        }
+    }
+
+    // Records the line table of a function, read from the source locations cranelift kept
+    // for its instructions
+    pub fn add_function(&mut self, id: FuncId, name: &str, line: usize, code: &CompiledCode) {
+        let mut rows: Vec<(u64, u64)> = vec![(0, line as u64)];
+        for srcloc in code.buffer.get_srclocs_sorted() {
+            let line = srcloc.loc.bits() as u64;
+            if !srcloc.loc.is_default() && rows.last().is_some_and(|&(_, last)| last != line) {
+                rows.push((srcloc.start as u64, line));
+            }
+        }
+        self.functions.push(FunctionInfo {
+            id,
+            name: name.to_string(),
+            line: line as u64,
+            size: code.code_info().total_size as u64,
+            rows,
+        });
+    }
+
+    // Adds the `.debug_*` sections to the object, relocated against the function symbols
+    pub fn write(&self, product: &mut ObjectProduct) -> Result<()> {
+        let encoding = Encoding {
+            format: Format::Dwarf32,
+            version: 4,
+            address_size: 8,
+        };
+        let comp_dir = env::current_dir()?.to_string_lossy().into_owned();
+
+        let mut dwarf = DwarfUnit::new(encoding);
+        dwarf.unit.line_program = LineProgram::new(
+            encoding,
+            LineEncoding::default(),
+            LineString::String(comp_dir.clone().into_bytes()),
+            LineString::String(self.source_name.clone().into_bytes()),
+            None,
+        );
+        let directory = dwarf.unit.line_program.default_directory();
+        let file = dwarf.unit.line_program.add_file(
+            LineString::String(self.source_name.clone().into_bytes()),
+            directory,
+            None,
+        );
+
+        let root = dwarf.unit.root();
+        let mut ranges = vec![];
+        for (symbol, function) in self.functions.iter().enumerate() {
+            let address = Address::Symbol { symbol, addend: 0 };
+            dwarf.unit.line_program.begin_sequence(Some(address));
+            for &(offset, line) in &function.rows {
+                let row = dwarf.unit.line_program.row();
+                row.address_offset = offset;
+                row.file = file;
+                row.line = line;
+                dwarf.unit.line_program.generate_row();
+            }
+            dwarf.unit.line_program.end_sequence(function.size);
+            ranges.push(Range::StartLength {
+                begin: address,
+                length: function.size,
+            });
+
+            let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+            let entry = dwarf.unit.get_mut(subprogram);
+            let name = function.name.clone().into_bytes();
+            entry.set(gimli::DW_AT_name, AttributeValue::String(name));
+            entry.set(
+                gimli::DW_AT_decl_file,
+                AttributeValue::FileIndex(Some(file)),
+            );
+            entry.set(gimli::DW_AT_decl_line, AttributeValue::Udata(function.line));
+            entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(address));
+            entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(function.size));
+        }
+
+        let ranges = dwarf.unit.ranges.add(RangeList(ranges));
+        let entry = dwarf.unit.get_mut(root);
+        let name = self.source_name.clone().into_bytes();
+        entry.set(gimli::DW_AT_name, AttributeValue::String(name));
+        entry.set(
+            gimli::DW_AT_comp_dir,
+            AttributeValue::String(comp_dir.into_bytes()),
+        );
+        entry.set(
+            gimli::DW_AT_producer,
+            AttributeValue::String(b"aurora-compiler".to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(0)),
+        );
+        entry.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+
+        let mut sections = Sections::new(RelocatingWriter::new());
+        dwarf.write(&mut sections).map_err(debug_error)?;
+
+        let object = &mut product.object;
+        let mut section_ids = HashMap::new();
+        sections.for_each(|id, writer| {
+            if !writer.bytes.slice().is_empty() {
+                let section =
+                    object.add_section(vec![], id.name().as_bytes().to_vec(), SectionKind::Debug);
+                object.set_section_data(section, writer.bytes.slice().to_vec(), 1);
+                section_ids.insert(id, section);
+            }
+            Ok::<_, Error>(())
+        })?;
+
+        sections.for_each(|id, writer| {
+            let Some(&section) = section_ids.get(&id) else {
+                return Ok(());
+            };
+            for relocation in &writer.relocations {
+                let symbol = match relocation.target {
+                    Target::Function(index) => product.function_symbol(self.functions[index].id),
+                    Target::Section(id) => product.object.section_symbol(section_ids[&id]),
+                };
+                let flags = RelocationFlags::Generic {
+                    kind: RelocationKind::Absolute,
+                    encoding: RelocationEncoding::Generic,
+                    size: relocation.size * 8,
+                };
+                product
+                    .object
+                    .add_relocation(
+                        section,
+                        Relocation {
+                            offset: relocation.offset as u64,
+                            symbol,
+                            addend: relocation.addend,
+                            flags,
+                        },
+                    )
+                    .map_err(debug_error)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn debug_error(error: impl ToString) -> Error {
+    Error::DebugInfo(error.to_string())
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    // Index of the function in `DebugInfo::functions`
+    Function(usize),
+    Section(SectionId),
+}
+
+#[derive(Clone)]
+struct DebugRelocation {
+    offset: usize,
+    target: Target,
+    addend: i64,
+    size: u8,
+}
+
+// Keeps the addresses of functions and the offsets into other debug sections as
+// relocations, since neither is known until link time
+#[derive(Clone)]
+struct RelocatingWriter {
+    bytes: EndianVec<RunTimeEndian>,
+    relocations: Vec<DebugRelocation>,
+}
+
+impl RelocatingWriter {
+    fn new() -> Self {
+        Self {
+            bytes: EndianVec::new(RunTimeEndian::Little),
+            relocations: vec![],
+        }
+    }
+}
+
+impl Writer for RelocatingWriter {
+    type Endian = RunTimeEndian;
+
+    fn endian(&self) -> Self::Endian {
+        self.bytes.endian()
+    }
+
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> gimli::write::Result<()> {
+        self.bytes.write(bytes)
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> gimli::write::Result<()> {
+        self.bytes.write_at(offset, bytes)
+    }
+
+    fn write_address(&mut self, address: Address, size: u8) -> gimli::write::Result<()> {
+        match address {
+            Address::Constant(value) => self.write_udata(value, size),
+            Address::Symbol { symbol, addend } => {
+                self.relocations.push(DebugRelocation {
+                    offset: self.len(),
+                    target: Target::Function(symbol),
+                    addend,
+                    size,
+                });
+                self.write_udata(0, size)
+            }
+        }
+    }
+
+    fn write_offset(
+        &mut self,
+        value: usize,
+        section: SectionId,
+        size: u8,
+    ) -> gimli::write::Result<()> {
+        let offset = self.len();
+        self.relocations.push(DebugRelocation {
+            offset,
+            target: Target::Section(section),
+            addend: value as i64,
+            size,
+        });
+        self.write_udata(0, size)
+    }
+
+    fn write_offset_at(
+        &mut self,
+        offset: usize,
+        value: usize,
+        section: SectionId,
+        size: u8,
+    ) -> gimli::write::Result<()> {
+        self.relocations.push(DebugRelocation {
+            offset,
+            target: Target::Section(section),
+            addend: value as i64,
+            size,
+        });
+        self.write_udata_at(offset, 0, size)
+    }
+}
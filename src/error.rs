@@ -1,45 +1,342 @@
 use std::fmt::{self, Debug, Formatter};
 use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
 use std::{io, num::ParseFloatError, result};
 
 use cranelift::codegen::CodegenError;
 use cranelift_module::ModuleError;
 
-use crate::lexer::Token;
+use crate::lexer::{Span, Token};
+use crate::source_map::SourceMap;
+use crate::typing::AuroraType;
 
 use self::Error::*;
 
 pub type Result<T> = result::Result<T, Error>;
 
 pub enum Error {
-    CraneliftCodegen(CodegenError),
-    CraneliftModule(ModuleError),
+    // Boxed as they're much larger than the other variants, which every `Result` would pay for
+    CraneliftCodegen(Box<CodegenError>),
+    CraneliftModule(Box<ModuleError>),
     Io(io::Error),
+    // An IO error and the file it happened on
+    IoWithPath(PathBuf, io::Error),
     ParseFloat(ParseFloatError),
     ParseInt(ParseIntError),
     UnknownChar(char),
     Undefined(String),
     Unexpected(&'static str),
-    UnexpectedToken(Token, Token),
+    // Boxed, as integer literals make a token 16 byte aligned and every error with it
+    UnexpectedToken(Box<Token>, Box<Token>),
     WrongArgumentCount,
+    VoidValueUsed(String),
+    VoidParameter(String),
+    NoMatchingOverload(String, Vec<AuroraType>),
+    AmbiguousOverload(String, Vec<AuroraType>),
+    UnknownParameter(String),
+    DuplicateArgument(String),
     VariableRedef,
+    AssignToImmutable(String),
+    Unassigned(String),
     FunctionRedef,
     FunctionRedefWithDifferentParams,
-    MismatchedTypes(&'static str, &'static str),
+    MismatchedTypes(AuroraType, AuroraType),
+    NonBoolCondition(AuroraType),
+    ChainedComparison,
+    OutsideLoop(&'static str),
+    UnknownLabel(String),
+    MissingReturn(AuroraType),
+    TopLevelWithMain,
+    NoMainFunction(String),
+    EntryWithMain(String),
+    InvalidEntry(String),
+    MissingReturnValue(AuroraType),
+    UnexpectedReturnValue(AuroraType),
+    UnterminatedString,
+    // The escape sequence as written, e.g. `\q`
+    InvalidEscape(String),
+    NonUtf8String,
+    MalformedNumber(String),
+    NumberOutOfRange(String),
+    DivisionByZero,
+    InvalidLiteralSuffix(String),
+    // Values are kept as text for the same reason
+    IntegerLiteralOutOfRange(String, AuroraType),
+    UnresolvedType(String),
+    NotIndexable(AuroraType),
+    NotDereferenceable(AuroraType),
+    NotMatchable(AuroraType),
+    NotPrintable(AuroraType),
+    InvalidCast(AuroraType, AuroraType),
+    MissingDefaultArm,
+    UnreachableArm,
+    NotAStruct(AuroraType),
+    UnknownField(AuroraType, String),
+    MissingField(AuroraType, String),
+    DuplicateField(String),
+    UnknownVariant(AuroraType, String),
+    DuplicateVariant(String),
+    TypeRedef(String),
+    IndexOutOfBounds(String, usize),
+    UnsupportedTarget(String),
+    UnknownArgument(String),
+    MissingArgumentValue(&'static str),
+    InvalidArgumentValue(&'static str, String),
+    LinkFailed(String),
+    // How a program the REPL ran ended
+    ProgramFailed(String),
+    Import(String, io::Error),
+    DebugInfo(String),
+    // A use of a variable whose `let` failed, never reported as that error already was
+    Poisoned,
+    // Reported after the first `--max-errors` errors, in place of the rest
+    TooManyErrors,
+    // A warning, failing the build under `--warnings-as-errors`
+    DeniedWarning(Warning),
+    Errors(Vec<Error>),
+    // An error and the line and column it was found at, before the file is known
+    Positioned(usize, usize, Box<Error>),
+    // An error and the file, line and column it was found at. Denied warnings only know
+    // their line
+    Located(String, usize, Option<usize>, Box<Error>),
+}
+
+impl Error {
+    // Wraps several errors into one, leaving a single error as is
+    pub fn from_vec(mut errors: Vec<Error>) -> Self {
+        if errors.len() == 1 {
+            errors.pop().unwrap()
+        } else {
+            Errors(errors)
+        }
+    }
+
+    // Wraps an IO error on the file at `path`, for `map_err`
+    pub fn io_at(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.as_ref().to_path_buf();
+        move |error| IoWithPath(path, error)
+    }
+
+    // Places an error at the start of the expression it's about, unless it already has a
+    // place. Code made up by the compiler has none to give
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            Errors(errors) => Errors(errors.into_iter().map(|error| error.at(span)).collect()),
+            Positioned(..) | Located(..) | Poisoned => self,
+            _ if span.start_line == 0 => self,
+            error => Positioned(span.start_line, span.start_col, Box::new(error)),
+        }
+    }
+
+    // Splits an aggregate into the errors it holds
+    pub fn into_vec(self) -> Vec<Error> {
+        match self {
+            Errors(errors) => errors,
+            error => vec![error],
+        }
+    }
+
+    // Prints every error to stderr, with the place it was found at when known
+    pub fn report(self, renderer: &dyn Renderer) {
+        for error in self.into_vec() {
+            let diagnostic = match error {
+                Located(file, line, pos, error) => Diagnostic {
+                    severity: Severity::Error,
+                    file: Some(file),
+                    line: Some(line),
+                    col: pos,
+                    message: format!("{:?}", error),
+                },
+                error => Diagnostic {
+                    severity: Severity::Error,
+                    file: None,
+                    line: None,
+                    col: None,
+                    message: format!("{:?}", error),
+                },
+            };
+            renderer.render(&diagnostic);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// An error or a warning as reported, with as much of its place as is known
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    pub message: String,
+}
+
+// Writes diagnostics to stderr in one of the `--error-format`s
+pub trait Renderer {
+    fn render(&self, diagnostic: &Diagnostic);
+}
+
+// `file:line:col Error: message`, for people, followed by the line it's on. The severity is
+// colored for a terminal
+pub struct Human {
+    color: bool,
+    sources: SourceMap,
+}
+
+impl Human {
+    pub fn new(color: bool) -> Self {
+        Self {
+            color,
+            sources: SourceMap::default(),
+        }
+    }
+}
+
+// One JSON object per line, for tools
+pub struct Json;
+
+impl Renderer for Human {
+    fn render(&self, diagnostic: &Diagnostic) {
+        let (severity, color) = match diagnostic.severity {
+            Severity::Error => ("Error", "\x1b[1;91m"),
+            Severity::Warning => ("Warning", "\x1b[1;93m"),
+        };
+        let severity = if self.color {
+            format!("{}{}\x1b[0m", color, severity)
+        } else {
+            severity.to_string()
+        };
+        let place: Vec<String> = diagnostic
+            .file
+            .iter()
+            .cloned()
+            .chain(diagnostic.line.map(|line| line.to_string()))
+            .chain(diagnostic.col.map(|col| col.to_string()))
+            .collect();
+        if place.is_empty() {
+            eprintln!("{}: {}", severity, diagnostic.message);
+        } else {
+            eprintln!("{} {}: {}", place.join(":"), severity, diagnostic.message);
+        }
+        if let (Some(file), Some(line)) = (&diagnostic.file, diagnostic.line) {
+            if let Some(snippet) = self.sources.snippet(file, line, diagnostic.col) {
+                eprintln!("{}", snippet);
+            }
+        }
+    }
+}
+
+impl Renderer for Json {
+    fn render(&self, diagnostic: &Diagnostic) {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let number = |number: Option<usize>| number.map_or("null".to_string(), |n| n.to_string());
+        eprintln!(
+            "{{\"file\":{},\"line\":{},\"col\":{},\"severity\":\"{}\",\"message\":{}}}",
+            diagnostic
+                .file
+                .as_deref()
+                .map_or("null".to_string(), json_string),
+            number(diagnostic.line),
+            number(diagnostic.col),
+            severity,
+            json_string(&diagnostic.message)
+        );
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::from('"');
+    for char in string.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            char if char.is_control() => json.push_str(&format!("\\u{:04x}", char as u32)),
+            char => json.push(char),
+        }
+    }
+    json.push('"');
+    json
+}
+
+pub enum Warning {
+    UnusedVariable(String),
+    UnreachableCode,
+    ShadowedFunction(String),
+}
+
+impl Debug for Warning {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Warning::UnusedVariable(name) => write!(formatter, "unused variable `{}`", name),
+            Warning::UnreachableCode => write!(formatter, "unreachable statement"),
+            Warning::ShadowedFunction(name) => {
+                write!(formatter, "variable `{}` has the name of a function", name)
+            }
+        }
+    }
 }
 
 impl Debug for Error {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Io(ref error) => error.fmt(formatter),
+            IoWithPath(path, error) => write!(formatter, "{}: {}", path.display(), error),
             ParseFloat(ref error) => error.fmt(formatter),
             ParseInt(ref error) => error.fmt(formatter),
             UnknownChar(char) => write!(formatter, "unknown char `{}`", char),
             Undefined(msg) => write!(formatter, "undefined {}", msg),
             Unexpected(msg) => write!(formatter, "unexpected {}", msg),
             WrongArgumentCount => write!(formatter, "wrong argument count"),
+            VoidValueUsed(name) => write!(
+                formatter,
+                "`{}` returns nothing, its result can't be used as a value",
+                name
+            ),
+            VoidParameter(name) => write!(formatter, "parameter `{}` can't be void", name),
+            AmbiguousOverload(name, types) => {
+                let types: Vec<String> = types.iter().map(|type_| type_.to_string()).collect();
+                write!(
+                    formatter,
+                    "more than one overload of `{}` takes ({})",
+                    name,
+                    types.join(", ")
+                )
+            }
+            NoMatchingOverload(name, types) => {
+                let types: Vec<String> = types.iter().map(|type_| type_.to_string()).collect();
+                write!(
+                    formatter,
+                    "no overload of `{}` takes ({})",
+                    name,
+                    types.join(", ")
+                )
+            }
+            UnknownParameter(name) => write!(formatter, "no parameter named `{}`", name),
+            DuplicateArgument(name) => {
+                write!(formatter, "argument `{}` is given more than once", name)
+            }
             FunctionRedef => write!(formatter, "redefinition of function"),
             VariableRedef => write!(formatter, "redefinition of a variable"),
+            AssignToImmutable(name) => {
+                write!(
+                    formatter,
+                    "cannot assign twice to immutable variable `{}`",
+                    name
+                )
+            }
+            Unassigned(name) => write!(
+                formatter,
+                "variable `{}` is read before it's assigned",
+                name
+            ),
             FunctionRedefWithDifferentParams => write!(
                 formatter,
                 "redefinition of function with different number of parameters"
@@ -56,6 +353,139 @@ impl Debug for Error {
                 "mismatched type, was expecting '{}' but got '{}'",
                 expected, got,
             ),
+            NonBoolCondition(got) => write!(
+                formatter,
+                "condition must be of type 'bool' but got '{}'",
+                got,
+            ),
+            ChainedComparison => write!(
+                formatter,
+                "comparison operators cannot be chained, use parentheses or '&&'"
+            ),
+            OutsideLoop(keyword) => write!(formatter, "`{}` outside of a loop", keyword),
+            UnknownLabel(label) => write!(formatter, "no enclosing loop labeled `'{}`", label),
+            MissingReturn(type_) => {
+                write!(formatter, "missing return of a value of type '{}'", type_)
+            }
+            TopLevelWithMain => write!(
+                formatter,
+                "top level statements can't be mixed with an explicit `fn main`"
+            ),
+            NoMainFunction(entry) => write!(
+                formatter,
+                "no function `{}` without parameters to start the executable at",
+                entry
+            ),
+            EntryWithMain(entry) => write!(
+                formatter,
+                "`main` can't be defined when the entry is `{}`",
+                entry
+            ),
+            InvalidEntry(entry) => write!(
+                formatter,
+                "entry function `{}` must return 'i32' or nothing",
+                entry
+            ),
+            MissingReturnValue(type_) => write!(
+                formatter,
+                "`return` without a value in a function returning '{}'",
+                type_
+            ),
+            UnexpectedReturnValue(type_) => write!(
+                formatter,
+                "cannot return a value of type '{}' from a function returning 'void'",
+                type_
+            ),
+            UnterminatedString => write!(formatter, "unterminated string literal"),
+            InvalidEscape(escape) => write!(formatter, "invalid escape sequence `{}`", escape),
+            NonUtf8String => write!(formatter, "string literal is not valid UTF-8"),
+            MalformedNumber(number) => write!(formatter, "malformed number literal `{}`", number),
+            NumberOutOfRange(number) => write!(
+                formatter,
+                "number literal `{}` doesn't fit in 128 bits",
+                number
+            ),
+            DivisionByZero => write!(formatter, "division by zero"),
+            InvalidLiteralSuffix(suffix) => {
+                write!(formatter, "invalid suffix `{}` for integer literal", suffix)
+            }
+            IntegerLiteralOutOfRange(value, type_) => write!(
+                formatter,
+                "literal `{}` is out of range for type '{}'",
+                value, type_,
+            ),
+            UnresolvedType(name) => write!(
+                formatter,
+                "cannot infer the type of `{}`, annotate it or assign to it before use",
+                name
+            ),
+            NotIndexable(type_) => {
+                write!(formatter, "cannot index into a value of type '{}'", type_)
+            }
+            NotDereferenceable(type_) => {
+                write!(formatter, "cannot dereference a value of type '{}'", type_)
+            }
+            InvalidCast(from, to) => write!(
+                formatter,
+                "cannot convert a value of type '{}' to '{}'",
+                from, to
+            ),
+            NotPrintable(type_) => write!(formatter, "cannot print a value of type '{}'", type_),
+            NotMatchable(type_) => {
+                write!(formatter, "cannot match on a value of type '{}'", type_)
+            }
+            MissingDefaultArm => write!(formatter, "`match` is missing its `_` arm"),
+            UnreachableArm => write!(
+                formatter,
+                "unreachable arm, its pattern is matched by an earlier one"
+            ),
+            NotAStruct(type_) => {
+                write!(formatter, "a value of type '{}' has no fields", type_)
+            }
+            UnknownField(type_, name) => {
+                write!(formatter, "struct '{}' has no field `{}`", type_, name)
+            }
+            MissingField(type_, name) => {
+                write!(formatter, "missing field `{}` of struct '{}'", name, type_)
+            }
+            DuplicateField(name) => write!(formatter, "field `{}` is given more than once", name),
+            UnknownVariant(type_, name) => {
+                write!(formatter, "enum '{}' has no variant `{}`", type_, name)
+            }
+            DuplicateVariant(name) => write!(formatter, "variant `{}` is declared twice", name),
+            TypeRedef(name) => write!(formatter, "redefinition of type '{}'", name),
+            IndexOutOfBounds(index, length) => write!(
+                formatter,
+                "index {} is out of bounds for an array of length {}",
+                index, length
+            ),
+            UnsupportedTarget(target) => write!(formatter, "unsupported target `{}`", target),
+            UnknownArgument(arg) => write!(formatter, "unknown argument `{}`", arg),
+            MissingArgumentValue(flag) => write!(formatter, "missing value for `{}`", flag),
+            InvalidArgumentValue(flag, value) => {
+                write!(formatter, "invalid value `{}` for `{}`", value, flag)
+            }
+            LinkFailed(linker) => write!(formatter, "linking with `{}` failed", linker),
+            ProgramFailed(status) => write!(formatter, "the program failed with {}", status),
+            Import(path, error) => write!(formatter, "cannot import `{}`: {}", path, error),
+            DebugInfo(msg) => write!(formatter, "failed to write debug info: {}", msg),
+            Poisoned => write!(formatter, "use of a variable whose `let` failed"),
+            TooManyErrors => write!(formatter, "too many errors, aborting"),
+            DeniedWarning(warning) => write!(formatter, "{:?} (warnings are errors)", warning),
+            Errors(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(formatter)?;
+                    }
+                    error.fmt(formatter)?;
+                }
+                Ok(())
+            }
+            Positioned(line, pos, error) => write!(formatter, "{}:{} {:?}", line, pos, error),
+            Located(file, line, Some(pos), error) => {
+                write!(formatter, "{}:{}:{} {:?}", file, line, pos, error)
+            }
+            Located(file, line, None, error) => write!(formatter, "{}:{} {:?}", file, line, error),
         }
     }
 }
@@ -75,13 +505,13 @@ impl From<ParseFloatError> for Error {
 
 impl From<ModuleError> for Error {
     fn from(error: ModuleError) -> Self {
-        CraneliftModule(error)
+        CraneliftModule(Box::new(error))
     }
 }
 
 impl From<CodegenError> for Error {
     fn from(error: CodegenError) -> Self {
-        CraneliftCodegen(error)
+        CraneliftCodegen(Box::new(error))
     }
 }
 
@@ -1,24 +1,44 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    str::FromStr,
+    time::Instant,
+};
 
 use cranelift::{
     codegen::{
         entity::EntityRef,
-        ir::{condcodes::FloatCC, types, AbiParam, InstBuilder, Signature, Type, Value},
+        ir::{
+            condcodes::{FloatCC, IntCC},
+            types, AbiParam, ArgumentPurpose, Block, InstBuilder, MemFlags, Signature, SourceLoc,
+            StackSlotData, StackSlotKind, TrapCode, Type, Value,
+        },
         isa::{self},
-        settings::{self},
+        settings::{self, Configurable},
     },
-    frontend::{FunctionBuilder, FunctionBuilderContext, Variable},
+    frontend::{FunctionBuilder, FunctionBuilderContext, Switch, Variable},
 };
-use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use cranelift_module::{default_libcall_names, DataDescription, FuncId, Linkage, Module};
 use cranelift_object::{ObjectBuilder, ObjectModule};
-use target_lexicon::triple;
+use target_lexicon::Triple;
 
 use crate::{
-    ast::{BinaryOp, Expr, ExprType, Function, Parameter, Prototype},
+    ast::{Argument, BinaryOp, Expr, ExprType, Function, Intrinsic, Parameter, Prototype, UnaryOp},
+    debug::DebugInfo,
     error::{Error, Result},
-    typing::{self, get_type_from_str},
+    intern::Symbol,
+    opt,
+    options::{CompileOptions, FloatCompare, Overflow},
+    runtime,
+    timing::PassTimes,
+    typing::{self, AuroraType},
 };
 
+// Trap code of a failed `assert`
+const ASSERT_TRAP: TrapCode = TrapCode::User(1);
+// Trap code of an integer division by zero caught by `--checked-div`
+const DIVISION_TRAP: TrapCode = TrapCode::User(2);
+
 struct VariableBuilder {
     index: usize,
 }
@@ -44,44 +64,99 @@ impl VariableBuilder {
     }
 }
 
+#[derive(Clone, Copy)]
+struct Local {
+    variable: Variable,
+    mutable: bool,
+    // Immutable bindings declared without a value may still be assigned once. This is whether
+    // it may have been assigned on some path to the current point
+    initialized: bool,
+    // Whether it has been assigned on every path to the current point, so it can be read. A
+    // loop's body is taken to have run
+    assigned: bool,
+    // Loops around the declaration. An assignment in a loop inside them is repeated
+    loop_depth: usize,
+    // Whether the variable holds the address of a stack slot with the actual value,
+    // which is needed once its address is taken
+    spilled: bool,
+}
+
+// A declared function and what calls need to know of it
 struct CompiledFunction {
     defined: bool,
     id: FuncId,
-    param_count: usize,
-    return_count: usize,
+    signature: typing::Signature,
+}
+
+// Where a call of the function to itself in tail position jumps to, reusing the frame
+struct TailCall {
+    name: Symbol,
+    block: Block,
+    params: Vec<Variable>,
+}
+
+// Where `continue` and `break` jump to from the body of a loop
+struct LoopTargets {
+    label: Option<String>,
+    continue_block: Block,
+    exit_block: Block,
+    // Whether anything jumps to the exit, only a `break` does for a `loop`
+    exited: bool,
 }
 
 pub struct FunctionGenerator<'a> {
     builder: FunctionBuilder<'a>,
-    functions: &'a HashMap<String, CompiledFunction>,
+    functions: &'a HashMap<Symbol, CompiledFunction>,
     module: &'a mut ObjectModule,
     variable_builder: &'a mut VariableBuilder,
-    values: HashMap<String, Variable>,
+    // One map per enclosing block, the innermost last
+    values: Vec<HashMap<Symbol, Local>>,
+    // Variables whose address is taken somewhere in the function
+    address_taken: HashSet<Symbol>,
+    tail_call: Option<TailCall>,
+    // Targets of the enclosing loops, the innermost last
+    loops: Vec<LoopTargets>,
+    return_type: AuroraType,
+    // Where a returned array or struct is copied to, memory the caller provides
+    struct_return: Option<Value>,
+    overflow: Overflow,
+    float_compare: FloatCompare,
+    checked_div: bool,
 }
 
 pub struct Generator {
     builder_context: FunctionBuilderContext,
-    functions: HashMap<String, CompiledFunction>,
+    functions: HashMap<Symbol, CompiledFunction>,
     pub module: ObjectModule,
     variable_builder: VariableBuilder,
+    overflow: Overflow,
+    float_compare: FloatCompare,
+    checked_div: bool,
+    print_ast: bool,
+    emit_ir: bool,
+    emit_asm: bool,
+    explain_types: bool,
+    debug: Option<DebugInfo>,
+    pub times: PassTimes,
 }
 
 struct ParseExpr {
     pub value: Option<Value>,
+    // Whether the current block is terminated, by a `return`, `break` or `continue`
     pub is_return: bool,
 }
 
 impl ParseExpr {
     pub fn new(value: Option<Value>) -> Self {
         Self {
-            value: value,
+            value,
             is_return: false,
         }
     }
 
     pub fn new_return(value: Option<Value>) -> Self {
         Self {
-            value: value,
+            value,
             is_return: true,
         }
     }
@@ -102,23 +177,588 @@ impl ParseExpr {
 }
 
 impl<'a> FunctionGenerator<'a> {
-    fn cast(&mut self, value: Value, from: &'static str, to: &'static str) -> Result<Value> {
-        match self.functions.get(&format!("{}->{}", from, to)) {
+    fn cast(&mut self, value: Value, from: &AuroraType, to: &AuroraType) -> Result<Value> {
+        match self
+            .functions
+            .get(&Symbol::intern(&format!("{}->{}", from, to)))
+        {
             Some(func) => {
-                let local_func = self
-                    .module
-                    .declare_func_in_func(func.id, &mut self.builder.func);
-                let call = self.builder.ins().call(local_func, &vec![value]);
+                let local_func = self.module.declare_func_in_func(func.id, self.builder.func);
+                let call = self.builder.ins().call(local_func, &[value]);
                 // TODO: Current solution is not the best
                 Ok(self.builder.inst_results(call)[0])
             }
-            None => return Err(Error::Undefined(format!("can't cast {} to {}", from, to))),
+            None => Err(Error::Undefined(format!("can't cast {} to {}", from, to))),
+        }
+    }
+
+    // Evaluates both operands of a binary operator, converting them to their common type
+    fn promote(&mut self, left: &Expr, right: &Expr) -> Result<(Value, Value, AuroraType)> {
+        let left_value = self.value(left)?;
+        let right_value = self.value(right)?;
+        // The typing pass rejects operands without one
+        let type_ = left.type_.promote(&right.type_).unwrap();
+        let left_value = self.convert(left_value, &left.type_, &type_)?;
+        let right_value = self.convert(right_value, &right.type_, &type_)?;
+        Ok((left_value, right_value, type_))
+    }
+
+    // Widens a numeric value, only ever called with types ordered by `AuroraType::promote`
+    fn convert(&mut self, value: Value, from: &AuroraType, to: &AuroraType) -> Result<Value> {
+        if from == to {
+            return Ok(value);
+        }
+        if to.is_float() && !from.is_float() {
+            return self.cast(value, from, to);
+        }
+        let type_ = to.get_type(self.pointer_type()).unwrap();
+        let value = if to.is_float() {
+            self.builder.ins().fpromote(type_, value)
+        } else if from.is_signed() {
+            self.builder.ins().sextend(type_, value)
+        } else {
+            self.builder.ins().uextend(type_, value)
+        };
+        Ok(value)
+    }
+
+    // Evaluates a returned value, converting literals and narrower numbers to the declared
+    // return type
+    fn return_value(&mut self, expr: &Expr) -> Result<Value> {
+        let return_type = self.return_type.clone();
+        let literal = match expr.expr_type {
+            ExprType::Integer(value) if return_type.fits_literal(value, &expr.type_) => {
+                Some(ExprType::Integer(value))
+            }
+            ExprType::Float(value) if return_type.is_float() => Some(ExprType::Float(value)),
+            _ => None,
+        };
+        if let Some(expr_type) = literal {
+            let literal = Expr {
+                expr_type,
+                type_: return_type,
+                span: expr.span,
+            };
+            return self.value(&literal);
+        }
+
+        let value = self.value(expr)?;
+        self.convert(value, &expr.type_, &return_type)
+    }
+
+    // Address of a nul-terminated copy of the string in the module's data
+    fn string(&mut self, string: &str) -> Result<Value> {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        let mut description = DataDescription::new();
+        description.define(bytes.into_boxed_slice());
+
+        let id = self.module.declare_anonymous_data(false, false)?;
+        self.module.define_data(id, &description)?;
+        let global = self.module.declare_data_in_func(id, self.builder.func);
+        let pointer_type = self.module.target_config().pointer_type();
+        Ok(self.builder.ins().global_value(pointer_type, global))
+    }
+
+    // Traps when the condition is false, reporting the message first if there's one
+    fn assert(&mut self, condition: &Expr, message: Option<&str>) -> Result<()> {
+        let condition = self.value(condition)?;
+        let fail_block = self.builder.create_block();
+        let continue_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(condition, continue_block, &[], fail_block, &[]);
+
+        self.builder.switch_to_block(fail_block);
+        self.builder.seal_block(fail_block);
+        if let Some(message) = message {
+            let message = self.string(message)?;
+            let report = &self.functions[&Symbol::intern("aurora_assert_failed")];
+            let report = self
+                .module
+                .declare_func_in_func(report.id, self.builder.func);
+            self.builder.ins().call(report, &[message]);
+        }
+        self.builder.ins().trap(ASSERT_TRAP);
+
+        self.builder.switch_to_block(continue_block);
+        self.builder.seal_block(continue_block);
+        Ok(())
+    }
+
+    // Jumps to the block of the arm matching the value. Switch lowers dense patterns to a
+    // jump table and sparse ones to a tree of comparisons
+    fn match_(&mut self, value: &Expr, arms: &[(i128, Expr)], default: &Expr) -> Result<ParseExpr> {
+        let bits = value.type_.get_type(self.pointer_type()).unwrap().bits();
+        let value = self.value(value)?;
+        let merge_block = self.builder.create_block();
+        let default_block = self.builder.create_block();
+
+        // Patterns are keyed by their unsigned bits in the value's width
+        let mask = u128::MAX >> (128 - bits);
+        let mut switch = Switch::new();
+        let mut blocks = vec![];
+        for (pattern, _) in arms {
+            let block = self.builder.create_block();
+            switch.set_entry(*pattern as u128 & mask, block);
+            blocks.push(block);
+        }
+        switch.emit(&mut self.builder, value, default_block);
+
+        let mut all_return = true;
+        let before = self.values.clone();
+        let mut arm_values = vec![];
+        let bodies = arms.iter().map(|(_, body)| body).chain([default]);
+        for (body, block) in bodies.zip(blocks.into_iter().chain([default_block])) {
+            self.builder.switch_to_block(block);
+            self.builder.seal_block(block);
+            if !self.expr(body)?.is_return {
+                self.builder.ins().jump(merge_block, &[]);
+                all_return = false;
+                arm_values.push(mem::replace(&mut self.values, before.clone()));
+            } else {
+                self.values = before.clone();
+            }
+        }
+        self.merge_initialized(&arm_values);
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        if all_return {
+            Ok(ParseExpr::empty_return())
+        } else {
+            Ok(ParseExpr::empty())
+        }
+    }
+
+    // The value of an expression used as an operand, which a call to a `void` function
+    // doesn't have
+    fn value(&mut self, expr: &Expr) -> Result<Value> {
+        self.expr(expr)?.value.ok_or_else(|| void_value(expr))
+    }
+
+    // Targets of the loop with the label, or of the innermost loop. The parser already
+    // checked the label names an enclosing loop
+    fn loop_targets(&mut self, label: &Option<String>) -> &mut LoopTargets {
+        match label {
+            Some(label) => self
+                .loops
+                .iter_mut()
+                .rev()
+                .find(|targets| targets.label.as_ref() == Some(label))
+                .unwrap(),
+            None => self.loops.last_mut().unwrap(),
+        }
+    }
+
+    // Short-circuits `&&`/`||`, only evaluating the right operand when it decides the result
+    fn logical(&mut self, op: BinaryOp, left: &Expr, right: &Expr) -> Result<Value> {
+        let left_value = self.value(left)?;
+
+        let right_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, types::I8);
+
+        match op {
+            BinaryOp::And => {
+                self.builder
+                    .ins()
+                    .brif(left_value, right_block, &[], merge_block, &[left_value])
+            }
+            _ => self
+                .builder
+                .ins()
+                .brif(left_value, merge_block, &[left_value], right_block, &[]),
+        };
+
+        self.builder.switch_to_block(right_block);
+        self.builder.seal_block(right_block);
+        let right_value = self.value(right)?;
+        self.builder.ins().jump(merge_block, &[right_value]);
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        Ok(self.builder.block_params(merge_block)[0])
+    }
+
+    // Shifts keep the type of the left operand, the amount being any integer. Right shifts
+    // of signed integers are arithmetic
+    fn shift(&mut self, op: BinaryOp, left: &Expr, right: &Expr) -> Result<Value> {
+        let left_value = self.value(left)?;
+        let right_value = self.value(right)?;
+        let value = match op {
+            BinaryOp::ShiftLeft => self.builder.ins().ishl(left_value, right_value),
+            _ if left.type_.is_unsigned() => self.builder.ins().ushr(left_value, right_value),
+            _ => self.builder.ins().sshr(left_value, right_value),
+        };
+        Ok(value)
+    }
+
+    // Cranelift wants the immediate of a type narrower than 64 bits without the sign extension,
+    // so `-1i8` is given as 0xff
+    fn iconst(&mut self, type_: Type, value: i64) -> Value {
+        let value = if type_.bits() < 64 {
+            value & ((1 << type_.bits()) - 1)
+        } else {
+            value
+        };
+        self.builder.ins().iconst(type_, value)
+    }
+
+    fn integer_arithmetic(
+        &mut self,
+        op: BinaryOp,
+        unsigned: bool,
+        left: Value,
+        right: Value,
+    ) -> Result<Value> {
+        let value = match self.overflow {
+            Overflow::Wrap => self.wrapping(op, left, right),
+            Overflow::Trap if self.builder.func.dfg.value_type(left) == types::I128 => {
+                self.checked_i128_arithmetic(op, unsigned, left, right)?
+            }
+            Overflow::Trap => {
+                let (value, overflowed) = match (op, unsigned) {
+                    (BinaryOp::Plus, false) => self.builder.ins().sadd_overflow(left, right),
+                    (BinaryOp::Plus, true) => self.builder.ins().uadd_overflow(left, right),
+                    (BinaryOp::Minus, false) => self.builder.ins().ssub_overflow(left, right),
+                    (BinaryOp::Minus, true) => self.builder.ins().usub_overflow(left, right),
+                    (_, false) => self.builder.ins().smul_overflow(left, right),
+                    (_, true) => self.builder.ins().umul_overflow(left, right),
+                };
+                self.builder
+                    .ins()
+                    .trapnz(overflowed, TrapCode::IntegerOverflow);
+                value
+            }
+        };
+        Ok(value)
+    }
+
+    fn wrapping(&mut self, op: BinaryOp, left: Value, right: Value) -> Value {
+        match op {
+            BinaryOp::Plus => self.builder.ins().iadd(left, right),
+            BinaryOp::Minus => self.builder.ins().isub(left, right),
+            _ => self.builder.ins().imul(left, right),
+        }
+    }
+
+    // Clamps a result past the range of the type to its bound. Cranelift's saturating
+    // instructions only take vectors, so the overflow checking ones are used instead
+    fn saturating(
+        &mut self,
+        op: BinaryOp,
+        unsigned: bool,
+        left: Value,
+        right: Value,
+    ) -> Result<Value> {
+        let type_ = self.builder.func.dfg.value_type(left);
+        if type_ == types::I128 {
+            return Err(Error::Unexpected(
+                "saturating arithmetic on a 128-bit integer",
+            ));
+        }
+        let (value, overflowed) = match (op, unsigned) {
+            (BinaryOp::Plus, false) => self.builder.ins().sadd_overflow(left, right),
+            (BinaryOp::Plus, true) => self.builder.ins().uadd_overflow(left, right),
+            (BinaryOp::Minus, false) => self.builder.ins().ssub_overflow(left, right),
+            (BinaryOp::Minus, true) => self.builder.ins().usub_overflow(left, right),
+            (_, false) => self.builder.ins().smul_overflow(left, right),
+            (_, true) => self.builder.ins().umul_overflow(left, right),
+        };
+        let bound = if unsigned {
+            // Only a subtraction goes below zero
+            let bound = if op == BinaryOp::Minus { 0 } else { -1 };
+            self.iconst(type_, bound)
+        } else {
+            // The exact result is below the minimum when it's negative
+            let negative = match op {
+                BinaryOp::Plus => self.builder.ins().icmp_imm(IntCC::SignedLessThan, right, 0),
+                BinaryOp::Minus => {
+                    self.builder
+                        .ins()
+                        .icmp_imm(IntCC::SignedGreaterThanOrEqual, right, 0)
+                }
+                _ => {
+                    let signs = self.builder.ins().bxor(left, right);
+                    self.builder.ins().icmp_imm(IntCC::SignedLessThan, signs, 0)
+                }
+            };
+            let shift = 64 - type_.bits();
+            let min = self.iconst(type_, i64::MIN >> shift);
+            let max = self.iconst(type_, i64::MAX >> shift);
+            self.builder.ins().select(negative, min, max)
+        };
+        Ok(self.builder.ins().select(overflowed, bound, value))
+    }
+
+    // The overflow checking instructions only go up to 64 bits, so for 128-bit integers the
+    // check is done from the sign bits, or for unsigned ones by comparing with an operand
+    fn checked_i128_arithmetic(
+        &mut self,
+        op: BinaryOp,
+        unsigned: bool,
+        left: Value,
+        right: Value,
+    ) -> Result<Value> {
+        let (value, overflowed) = match (op, unsigned) {
+            (BinaryOp::Plus, false) => {
+                let value = self.builder.ins().iadd(left, right);
+                // Both operands have a sign different from the result's
+                let left_flipped = self.builder.ins().bxor(left, value);
+                let right_flipped = self.builder.ins().bxor(right, value);
+                let flipped = self.builder.ins().band(left_flipped, right_flipped);
+                let overflowed = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::SignedLessThan, flipped, 0);
+                (value, overflowed)
+            }
+            (BinaryOp::Plus, true) => {
+                let value = self.builder.ins().iadd(left, right);
+                let overflowed = self
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedLessThan, value, left);
+                (value, overflowed)
+            }
+            (BinaryOp::Minus, false) => {
+                let value = self.builder.ins().isub(left, right);
+                // The operands have different signs and the result's differs from the left's
+                let operands = self.builder.ins().bxor(left, right);
+                let left_flipped = self.builder.ins().bxor(left, value);
+                let flipped = self.builder.ins().band(operands, left_flipped);
+                let overflowed = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::SignedLessThan, flipped, 0);
+                (value, overflowed)
+            }
+            (BinaryOp::Minus, true) => {
+                let value = self.builder.ins().isub(left, right);
+                let overflowed = self
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedLessThan, left, right);
+                (value, overflowed)
+            }
+            _ => {
+                return Err(Error::Unexpected(
+                    "overflow checked multiplication of a 128-bit integer",
+                ))
+            }
+        };
+        self.builder
+            .ins()
+            .trapnz(overflowed, TrapCode::IntegerOverflow);
+        Ok(value)
+    }
+
+    fn pointer_type(&self) -> Type {
+        self.module.target_config().pointer_type()
+    }
+
+    fn declare(&mut self, name: Symbol, local: Local) {
+        let scope = self.values.last_mut().unwrap();
+        scope.insert(name, local);
+    }
+
+    // Allocates a stack slot of the given size, returning its address
+    fn stack_slot(&mut self, size: u32) -> Value {
+        let data = StackSlotData::new(StackSlotKind::ExplicitSlot, size);
+        let slot = self.builder.create_sized_stack_slot(data);
+        let pointer_type = self.pointer_type();
+        self.builder.ins().stack_addr(pointer_type, slot, 0)
+    }
+
+    // Each arm of a branch starts from the bindings before it. After it, a binding may be
+    // assigned if it is in any arm that doesn't return, and is assigned if it is in all of them
+    fn merge_initialized(&mut self, arms: &[Vec<HashMap<Symbol, Local>>]) {
+        for (depth, scope) in self.values.iter_mut().enumerate() {
+            for (name, local) in scope.iter_mut() {
+                let arm_locals = arms.iter().map(|arm| arm[depth].get(name));
+                local.initialized |= arm_locals
+                    .clone()
+                    .any(|arm_local| arm_local.is_some_and(|local| local.initialized));
+                local.assigned |= !arms.is_empty()
+                    && arm_locals
+                        .into_iter()
+                        .all(|arm_local| arm_local.is_some_and(|local| local.assigned));
+            }
+        }
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<Local> {
+        self.values
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name))
+            .copied()
+    }
+
+    fn lookup_mut(&mut self, name: Symbol) -> Option<&mut Local> {
+        self.values
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name))
+    }
+
+    // Address of an array element as a base and an offset. Literal indices are checked
+    // here, the others trap at runtime when out of bounds
+    fn element_address(&mut self, array: &Expr, index: &Expr) -> Result<(Value, i32)> {
+        let AuroraType::Array(element, length) = &array.type_ else {
+            return Err(Error::NotIndexable(array.type_.clone()));
+        };
+        let size = element.get_type(self.pointer_type()).unwrap().bytes() as i64;
+        let address = self.value(array)?;
+        if let ExprType::Integer(index) = index.expr_type {
+            if index < 0 || index as usize >= *length {
+                return Err(Error::IndexOutOfBounds(index.to_string(), *length));
+            }
+            return Ok((address, (index as i64 * size) as i32));
+        }
+
+        let pointer_type = self.module.target_config().pointer_type();
+        let mut value = self.value(index)?;
+        let index_type = index.type_.get_type(pointer_type).unwrap();
+        // A wider index is checked in its own width, narrowing it first would drop the bits
+        // that put it out of bounds
+        let wide = index_type.bits() > pointer_type.bits();
+        if index_type.bits() < pointer_type.bits() {
+            value = if index.type_.is_signed() {
+                self.builder.ins().sextend(pointer_type, value)
+            } else {
+                self.builder.ins().uextend(pointer_type, value)
+            };
+        }
+        let mut bound = self.builder.ins().iconst(pointer_type, *length as i64);
+        if wide {
+            bound = self.builder.ins().uextend(index_type, bound);
+        }
+        let in_bounds = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedLessThan, value, bound);
+        self.builder
+            .ins()
+            .trapz(in_bounds, TrapCode::HeapOutOfBounds);
+        if wide {
+            value = self.builder.ins().ireduce(pointer_type, value);
+        }
+        let offset = self.builder.ins().imul_imm(value, size);
+        Ok((self.builder.ins().iadd(address, offset), 0))
+    }
+
+    // Address of a struct's field as a base and an offset
+    fn field_address(&mut self, struct_: &Expr, name: &str) -> Result<(Value, i32)> {
+        let Some((offset, _)) = struct_.type_.field(name, self.pointer_type()) else {
+            return Err(Error::UnknownField(struct_.type_.clone(), name.to_string()));
+        };
+        let address = self.value(struct_)?;
+        Ok((address, offset as i32))
+    }
+
+    // Stores a value of the given type, copying the memory of arrays and structs
+    fn store(&mut self, value: Value, type_: &AuroraType, address: Value, offset: i32) {
+        if !type_.is_aggregate() {
+            self.builder
+                .ins()
+                .store(MemFlags::trusted(), value, address, offset);
+            return;
+        }
+        let config = self.module.target_config();
+        let size = type_.size(config.pointer_type());
+        let align = type_.align(config.pointer_type()) as u8;
+        let destination = self.builder.ins().iadd_imm(address, offset as i64);
+        self.builder.emit_small_memory_copy(
+            config,
+            destination,
+            value,
+            size as u64,
+            align,
+            align,
+            true,
+            MemFlags::trusted(),
+        );
+    }
+
+    // Evaluates the arguments of a call, which the typing pass put in the order of the
+    // parameters, converting each to the type it's passed as
+    fn arguments(&mut self, args: &[&Expr], arg_types: &[AuroraType]) -> Result<Vec<Value>> {
+        let mut values = vec![];
+        for (arg, arg_type) in args.iter().zip(arg_types) {
+            let value = self.value(arg)?;
+            values.push(self.convert(value, &arg.type_, arg_type)?);
+        }
+        Ok(values)
+    }
+
+    fn is_tail_call(&self, expr: &Expr) -> bool {
+        match (&self.tail_call, &expr.expr_type) {
+            (Some(tail_call), ExprType::Call(name, _)) => *name == tail_call.name,
+            _ => false,
+        }
+    }
+
+    // Rebinds the parameters to the new arguments and jumps back to the start of the body,
+    // so tail recursion runs in constant stack space. Cranelift's `return_call` would need
+    // the `tail` calling convention, which C callers of the function can't use
+    fn tail_call(&mut self, args: &[Argument]) -> Result<ParseExpr> {
+        let tail_call = self.tail_call.as_ref().unwrap();
+        let (block, params) = (tail_call.block, tail_call.params.clone());
+        let param_types = self.functions[&tail_call.name]
+            .signature
+            .param_types
+            .clone();
+        let args: Vec<&Expr> = args.iter().map(|arg| &arg.value).collect();
+        let values = self.arguments(&args, &param_types)?;
+        // Every argument is evaluated before any parameter changes
+        for (param, value) in params.into_iter().zip(values) {
+            self.builder.def_var(param, value);
+        }
+        self.builder.ins().jump(block, &[]);
+        Ok(ParseExpr::empty_return())
+    }
+
+    // Keeps going after a failing statement so every error in the body gets reported. Only
+    // the body of a function gets a `return` when it doesn't end in one, a nested block
+    // falls through to the code after it
+    fn block(&mut self, exprs: &[Expr], implicit_return: bool) -> Result<ParseExpr> {
+        let mut errors = Vec::new();
+        let mut result = ParseExpr::empty();
+        self.values.push(HashMap::new());
+        for expr in exprs {
+            match self.expr(expr) {
+                Ok(parse_expr) if parse_expr.is_return => {
+                    result = parse_expr;
+                    break;
+                }
+                Ok(_) => (),
+                Err(error) => errors.extend(error.into_vec()),
+            }
         }
+        self.values.pop();
+        if !errors.is_empty() {
+            return Err(Error::from_vec(errors));
+        }
+        if implicit_return && !result.is_return {
+            if self.return_type != AuroraType::Void {
+                return Err(Error::MissingReturn(self.return_type.clone()));
+            }
+            self.builder.ins().return_(&[]);
+            result = ParseExpr::empty_return();
+        }
+        Ok(result)
     }
 
+    // Errors are placed at the innermost expression they come from
     fn expr(&mut self, expr: &Expr) -> Result<ParseExpr> {
+        self.generate(expr).map_err(|error| error.at(expr.span))
+    }
+
+    fn generate(&mut self, expr: &Expr) -> Result<ParseExpr> {
         let value = match &expr.expr_type {
-            ExprType::Float(num) => match get_type_from_str(expr.type_) {
+            ExprType::Float(num) => match expr.type_.get_type(self.pointer_type()) {
                 Some(type_) => match type_ {
                     types::F32 => ParseExpr::new(Some(self.builder.ins().f32const(*num as f32))),
                     types::F64 => ParseExpr::new(Some(self.builder.ins().f64const(*num))),
@@ -126,73 +766,338 @@ impl<'a> FunctionGenerator<'a> {
                 },
                 None => ParseExpr::empty(),
             },
-            ExprType::Integer(num) => match get_type_from_str(expr.type_) {
+            ExprType::Integer(num) => match expr.type_.get_type(self.pointer_type()) {
                 Some(type_) => match type_ {
-                    types::I8 => ParseExpr::new(Some(self.builder.ins().iconst(types::I8, *num))),
-                    types::I16 => ParseExpr::new(Some(self.builder.ins().iconst(types::I16, *num))),
-                    types::I32 => ParseExpr::new(Some(self.builder.ins().iconst(types::I32, *num))),
-                    types::I64 => ParseExpr::new(Some(self.builder.ins().iconst(types::I64, *num))),
+                    // Literals fit their type, an unsigned one may only look negative as an i64
+                    types::I8 | types::I16 | types::I32 | types::I64 => {
+                        ParseExpr::new(Some(self.iconst(type_, *num as i64)))
+                    }
+                    // Constants are at most 64 bits wide, so a 128-bit one is made of two halves
+                    types::I128 => {
+                        let low = self.builder.ins().iconst(types::I64, *num as i64);
+                        let high = self.builder.ins().iconst(types::I64, (*num >> 64) as i64);
+                        ParseExpr::new(Some(self.builder.ins().iconcat(low, high)))
+                    }
                     _ => unimplemented!(),
                 },
                 None => ParseExpr::empty(),
             },
-            ExprType::Variable(name) => match self.values.get(name) {
-                Some(&variable) => ParseExpr::new(Some(self.builder.use_var(variable))),
+            ExprType::Boolean(value) => {
+                ParseExpr::new(Some(self.builder.ins().iconst(types::I8, *value as i64)))
+            }
+            ExprType::Str(string) => ParseExpr::new(Some(self.string(string)?)),
+            ExprType::Variable(name) => match self.lookup(*name) {
+                Some(local) if !local.assigned => {
+                    return Err(Error::Unassigned(name.to_string()));
+                }
+                Some(local) if local.spilled => {
+                    let type_ = expr.type_.get_type(self.pointer_type()).unwrap();
+                    let address = self.builder.use_var(local.variable);
+                    let value = self
+                        .builder
+                        .ins()
+                        .load(type_, MemFlags::trusted(), address, 0);
+                    ParseExpr::new(Some(value))
+                }
+                Some(local) => ParseExpr::new(Some(self.builder.use_var(local.variable))),
                 None => {
                     return Err(Error::Undefined(format!("variable {}", name)));
                 }
             },
+            ExprType::Index(array, index) => {
+                let (address, offset) = self.element_address(array, index)?;
+                let type_ = expr.type_.get_type(self.pointer_type()).unwrap();
+                let value = self
+                    .builder
+                    .ins()
+                    .load(type_, MemFlags::trusted(), address, offset);
+                ParseExpr::new(Some(value))
+            }
+            ExprType::AssignIndex(array, index, value) => {
+                if let ExprType::Variable(name) = &array.expr_type {
+                    if !self.lookup(*name).is_some_and(|local| local.mutable) {
+                        return Err(Error::AssignToImmutable(name.to_string()));
+                    }
+                }
+                let (address, offset) = self.element_address(array, index)?;
+                let value = self.value(value)?;
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), value, address, offset);
+                ParseExpr::new(Some(value))
+            }
+            ExprType::AddressOf(place) => match &place.expr_type {
+                // Arrays and structs are already held by address
+                ExprType::Variable(_) if place.type_.is_aggregate() => self.expr(place)?,
+                ExprType::Variable(name) => match self.lookup_mut(*name) {
+                    // It may be assigned through the pointer from then on
+                    Some(local) => {
+                        local.assigned = true;
+                        let variable = local.variable;
+                        ParseExpr::new(Some(self.builder.use_var(variable)))
+                    }
+                    None => return Err(Error::Undefined(format!("variable {}", name))),
+                },
+                ExprType::Index(array, index) => {
+                    let (address, offset) = self.element_address(array, index)?;
+                    ParseExpr::new(Some(self.builder.ins().iadd_imm(address, offset as i64)))
+                }
+                ExprType::Field(struct_, name) => {
+                    let (address, offset) = self.field_address(struct_, name)?;
+                    ParseExpr::new(Some(self.builder.ins().iadd_imm(address, offset as i64)))
+                }
+                _ => return Err(Error::Unexpected("operand of '&'")),
+            },
+            ExprType::Deref(pointer) => {
+                let address = self.value(pointer)?;
+                if expr.type_.is_aggregate() {
+                    ParseExpr::new(Some(address))
+                } else {
+                    let type_ = expr.type_.get_type(self.pointer_type()).unwrap();
+                    let value = self
+                        .builder
+                        .ins()
+                        .load(type_, MemFlags::trusted(), address, 0);
+                    ParseExpr::new(Some(value))
+                }
+            }
+            ExprType::AssignDeref(pointer, value) => {
+                let address = self.value(pointer)?;
+                let value = self.value(value)?;
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), value, address, 0);
+                ParseExpr::new(Some(value))
+            }
+            ExprType::Field(struct_, name) => {
+                let (address, offset) = self.field_address(struct_, name)?;
+                let value = if expr.type_.is_aggregate() {
+                    self.builder.ins().iadd_imm(address, offset as i64)
+                } else {
+                    let type_ = expr.type_.get_type(self.pointer_type()).unwrap();
+                    self.builder
+                        .ins()
+                        .load(type_, MemFlags::trusted(), address, offset)
+                };
+                ParseExpr::new(Some(value))
+            }
+            ExprType::Struct(values) => {
+                let pointer_type = self.pointer_type();
+                let address = self.stack_slot(expr.type_.size(pointer_type));
+                for (name, value) in values {
+                    let (offset, _) = expr.type_.field(name, pointer_type).unwrap();
+                    let field = self.value(value)?;
+                    self.store(field, &value.type_, address, offset as i32);
+                }
+                ParseExpr::new(Some(address))
+            }
+            ExprType::Assert(condition, message) => {
+                self.assert(condition, message.as_deref())?;
+                ParseExpr::empty()
+            }
+            ExprType::SizeOf(type_) => {
+                let size = type_.size(self.pointer_type());
+                ParseExpr::new(Some(self.builder.ins().iconst(types::I64, size as i64)))
+            }
+            ExprType::Cast(value) => {
+                let from = &value.type_;
+                let converted = self.value(value)?;
+                let type_ = expr.type_.get_type(self.pointer_type()).unwrap();
+                let converted = if expr.type_ == AuroraType::Bool {
+                    self.builder.ins().icmp_imm(IntCC::NotEqual, converted, 0)
+                } else if *from == AuroraType::Bool && type_ != types::I8 {
+                    // A bool is 0 or 1, the same in any width
+                    self.builder.ins().uextend(type_, converted)
+                } else if *from == AuroraType::Bool {
+                    converted
+                } else {
+                    self.convert(converted, from, &expr.type_)?
+                };
+                ParseExpr::new(Some(converted))
+            }
+            ExprType::Binary(op, left, right) if op.is_logical() => {
+                ParseExpr::new(Some(self.logical(*op, left, right)?))
+            }
+            ExprType::Unary(UnaryOp::BitNot, operand) => {
+                let value = self.value(operand)?;
+                ParseExpr::new(Some(self.builder.ins().bnot(value)))
+            }
+            ExprType::Unary(UnaryOp::Negate, operand) => {
+                let type_ = &operand.type_;
+                let value = if type_.is_float() {
+                    let value = self.value(operand)?;
+                    self.builder.ins().fneg(value)
+                } else {
+                    // Subtracted from zero so overflow is handled as for any subtraction
+                    let zero = Expr {
+                        expr_type: ExprType::Integer(0),
+                        type_: type_.clone(),
+                        span: expr.span,
+                    };
+                    let zero = self.value(&zero)?;
+                    let value = self.value(operand)?;
+                    self.integer_arithmetic(BinaryOp::Minus, false, zero, value)?
+                };
+                ParseExpr::new(Some(value))
+            }
+            ExprType::Intrinsic(intrinsic, left, right) => {
+                let unsigned = left.type_.is_unsigned();
+                let left = self.value(left)?;
+                let right = self.value(right)?;
+                let value = match intrinsic {
+                    Intrinsic::Wrapping(op) => self.wrapping(*op, left, right),
+                    Intrinsic::Saturating(op) => self.saturating(*op, unsigned, left, right)?,
+                };
+                ParseExpr::new(Some(value))
+            }
+            ExprType::Binary(op, left, right) if op.is_shift() => {
+                ParseExpr::new(Some(self.shift(*op, left, right)?))
+            }
             ExprType::Binary(op, left, right) => {
-                let left_value = self.expr(&left)?.value.unwrap(); // TODO: unwrap these properly
-                let mut right_value = self.expr(&right)?.value.unwrap();
+                let (left_value, right_value, type_) = self.promote(left, right)?;
                 match op {
-                    BinaryOp::Plus => match left.type_ {
-                        typing::I64 => {
-                            // TODO: Add more basic type conversions
-                            if right.type_ != left.type_ {
-                                return Err(Error::MismatchedTypes(left.type_, right.type_));
-                            }
-                            ParseExpr::new(Some(self.builder.ins().iadd(left_value, right_value)))
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                    BinaryOp::ShiftLeft | BinaryOp::ShiftRight => unreachable!(),
+                    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => {
+                        let value = match op {
+                            BinaryOp::BitAnd => self.builder.ins().band(left_value, right_value),
+                            BinaryOp::BitOr => self.builder.ins().bor(left_value, right_value),
+                            _ => self.builder.ins().bxor(left_value, right_value),
+                        };
+                        ParseExpr::new(Some(value))
+                    }
+                    BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Times => {
+                        if type_.is_integer() {
+                            ParseExpr::new(Some(self.integer_arithmetic(
+                                *op,
+                                type_.is_unsigned(),
+                                left_value,
+                                right_value,
+                            )?))
+                        } else {
+                            let value = match op {
+                                BinaryOp::Plus => self.builder.ins().fadd(left_value, right_value),
+                                BinaryOp::Minus => self.builder.ins().fsub(left_value, right_value),
+                                _ => self.builder.ins().fmul(left_value, right_value),
+                            };
+                            ParseExpr::new(Some(value))
                         }
-                        typing::F64 => {
-                            // TODO: Change this into a function
-                            if right.type_ != left.type_ {
-                                right_value = self.cast(right_value, right.type_, left.type_)?;
-                            }
-                            ParseExpr::new(Some(self.builder.ins().fadd(left_value, right_value)))
+                    }
+                    BinaryOp::Divide | BinaryOp::Modulo => {
+                        if let ExprType::Integer(0) = right.expr_type {
+                            return Err(Error::DivisionByZero);
                         }
-                        _ => return Err(Error::Unexpected("can't add this type")),
-                    },
-                    BinaryOp::Minus => {
-                        ParseExpr::new(Some(self.builder.ins().fsub(left_value, right_value)))
+                        // Cranelift doesn't lower 128-bit division, it would need a libcall
+                        // to compiler-rt's `__divti3` and friends
+                        if type_.get_type(self.pointer_type()) == Some(types::I128) {
+                            return Err(Error::Unexpected("division of a 128-bit integer"));
+                        }
+                        if self.checked_div && !type_.is_float() {
+                            self.builder.ins().trapz(right_value, DIVISION_TRAP);
+                        }
+                        let value = match (op, type_.is_float()) {
+                            // Only division, the typing pass rejects the modulo of a float
+                            (_, true) => self.builder.ins().fdiv(left_value, right_value),
+                            (BinaryOp::Divide, false) if type_.is_unsigned() => {
+                                self.builder.ins().udiv(left_value, right_value)
+                            }
+                            (BinaryOp::Divide, false) => {
+                                self.builder.ins().sdiv(left_value, right_value)
+                            }
+                            (_, false) if type_.is_unsigned() => {
+                                self.builder.ins().urem(left_value, right_value)
+                            }
+                            (_, false) => self.builder.ins().srem(left_value, right_value),
+                        };
+                        ParseExpr::new(Some(value))
                     }
-                    BinaryOp::Times => {
-                        ParseExpr::new(Some(self.builder.ins().fmul(left_value, right_value)))
+                    BinaryOp::LessThan
+                    | BinaryOp::LessEqual
+                    | BinaryOp::GreaterThan
+                    | BinaryOp::GreaterEqual
+                    | BinaryOp::Equal
+                    | BinaryOp::NotEqual => {
+                        let (int_cc, float_cc) = match op {
+                            BinaryOp::LessThan => (IntCC::SignedLessThan, FloatCC::LessThan),
+                            BinaryOp::LessEqual => {
+                                (IntCC::SignedLessThanOrEqual, FloatCC::LessThanOrEqual)
+                            }
+                            BinaryOp::GreaterThan => {
+                                (IntCC::SignedGreaterThan, FloatCC::GreaterThan)
+                            }
+                            BinaryOp::GreaterEqual => {
+                                (IntCC::SignedGreaterThanOrEqual, FloatCC::GreaterThanOrEqual)
+                            }
+                            BinaryOp::NotEqual => (IntCC::NotEqual, FloatCC::NotEqual),
+                            _ => (IntCC::Equal, FloatCC::Equal),
+                        };
+                        let float_cc = match (self.float_compare, float_cc) {
+                            (FloatCompare::Ordered, _) | (_, FloatCC::NotEqual) => float_cc,
+                            (_, FloatCC::LessThan) => FloatCC::UnorderedOrLessThan,
+                            (_, FloatCC::LessThanOrEqual) => FloatCC::UnorderedOrLessThanOrEqual,
+                            (_, FloatCC::GreaterThan) => FloatCC::UnorderedOrGreaterThan,
+                            (_, FloatCC::GreaterThanOrEqual) => {
+                                FloatCC::UnorderedOrGreaterThanOrEqual
+                            }
+                            _ => FloatCC::UnorderedOrEqual,
+                        };
+                        let int_cc = if type_.is_unsigned() {
+                            int_cc.unsigned()
+                        } else {
+                            int_cc
+                        };
+                        let value = if type_.is_float() {
+                            self.builder.ins().fcmp(float_cc, left_value, right_value)
+                        } else {
+                            self.builder.ins().icmp(int_cc, left_value, right_value)
+                        };
+                        ParseExpr::new(Some(value))
                     }
-                    BinaryOp::LessThan => ParseExpr::new(Some(self.builder.ins().fcmp(
-                        FloatCC::LessThan,
-                        left_value,
-                        right_value,
-                    ))),
                 }
             }
             ExprType::Call(name, args) => match self.functions.get(name) {
                 Some(func) => {
-                    if func.param_count != args.len() {
-                        return Err(Error::WrongArgumentCount);
+                    let local_func = self.module.declare_func_in_func(func.id, self.builder.func);
+                    let func = &func.signature;
+                    let args: Vec<&Expr> = args.iter().map(|arg| &arg.value).collect();
+                    let param_count = func.param_types.len();
+
+                    // C functions take narrower arguments than their parameters, and pass
+                    // variadic ones with the default argument promotions
+                    let mut arg_types = func.param_types.clone();
+                    for arg in &args[param_count..] {
+                        arg_types.push(if func.external {
+                            arg.type_.c_variadic()
+                        } else {
+                            arg.type_.clone()
+                        });
                     }
-                    let local_func = self
-                        .module
-                        .declare_func_in_func(func.id, &mut self.builder.func);
-                    let arguments: Result<Vec<_>> =
-                        args.into_iter().map(|arg| self.expr(&arg)).collect();
-                    let arguments: Vec<Value> = arguments?
-                        .into_iter()
-                        .map(|arg| arg.value.unwrap()) // TODO: Properly unwrap arguments
-                        .collect();
+
+                    // Cranelift has no variadic signatures, so the variadic arguments are
+                    // appended to this call site's own copy of the callee signature
+                    if func.variadic {
+                        let signature = self.builder.func.dfg.ext_funcs[local_func].signature;
+                        for arg_type in &arg_types[param_count..] {
+                            // A `void` argument is rejected by the typing pass
+                            let type_ = arg_type.get_type(self.pointer_type()).unwrap();
+                            self.builder.func.dfg.signatures[signature]
+                                .params
+                                .push(AbiParam::new(type_));
+                        }
+                    }
+                    let mut arguments = vec![];
+                    let struct_return = func.return_type.is_aggregate().then(|| {
+                        let size = func.return_type.size(self.pointer_type());
+                        self.stack_slot(size)
+                    });
+                    arguments.extend(struct_return);
+                    arguments.extend(self.arguments(&args, &arg_types)?);
 
                     let call = self.builder.ins().call(local_func, &arguments);
-                    if func.return_count > 0 {
+                    if struct_return.is_some() {
+                        return Ok(ParseExpr::new(struct_return));
+                    }
+                    if func.return_type != AuroraType::Void {
                         // TODO: Current solution is not the best
                         return Ok(ParseExpr::new(Some(self.builder.inst_results(call)[0])));
                     }
@@ -200,55 +1105,307 @@ impl<'a> FunctionGenerator<'a> {
                 }
                 None => return Err(Error::Undefined(format!("function {}", name))),
             },
-            ExprType::Block(exprs) => {
-                for expr in exprs {
-                    let parse_expr = self.expr(&expr)?;
-                    if parse_expr.is_return {
-                        return Ok(parse_expr);
-                    }
+            ExprType::Block(exprs) => self.block(exprs, false)?,
+            ExprType::IfElse(condition, then, else_) => {
+                let condition = self.value(condition)?;
+
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+
+                self.builder
+                    .ins()
+                    .brif(condition, then_block, &[], else_block, &[]);
+
+                let before = self.values.clone();
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+                let then_returns = self.expr(then)?.is_return;
+                if !then_returns {
+                    self.builder.ins().jump(merge_block, &[]);
+                }
+                let then_values = mem::replace(&mut self.values, before.clone());
+
+                self.builder.switch_to_block(else_block);
+                self.builder.seal_block(else_block);
+                let else_returns = match else_ {
+                    Some(else_) => self.expr(else_)?.is_return,
+                    None => false,
+                };
+                if !else_returns {
+                    self.builder.ins().jump(merge_block, &[]);
+                }
+                let else_values = mem::replace(&mut self.values, before);
+                let arms = [(then_returns, then_values), (else_returns, else_values)];
+                let arms: Vec<_> = arms
+                    .into_iter()
+                    .filter_map(|(returns, values)| (!returns).then_some(values))
+                    .collect();
+                self.merge_initialized(&arms);
+
+                // When both arms return the merge block has no predecessors. It's left
+                // empty, the enclosing block stopping at a statement that returns, so no
+                // terminator is added after the arms' own
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+
+                if then_returns && else_returns {
+                    ParseExpr::empty_return()
+                } else {
+                    ParseExpr::empty()
+                }
+            }
+            ExprType::Match(value, arms, default) => self.match_(value, arms, default)?,
+            ExprType::While(condition, body, label) => {
+                let header_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+
+                self.builder.ins().jump(header_block, &[]);
+
+                self.builder.switch_to_block(header_block);
+                let condition = self.value(condition)?;
+                self.builder
+                    .ins()
+                    .brif(condition, body_block, &[], exit_block, &[]);
+
+                self.builder.switch_to_block(body_block);
+                self.builder.seal_block(body_block);
+                self.loops.push(LoopTargets {
+                    label: label.clone(),
+                    continue_block: header_block,
+                    exit_block,
+                    exited: true,
+                });
+                let body = self.expr(body);
+                self.loops.pop();
+                // A body ending in a `return`, `break` or `continue` is already terminated, an
+                // `if` returning in only one arm isn't and still loops back
+                if !body?.is_return {
+                    self.builder.ins().jump(header_block, &[]);
                 }
-                self.builder.ins().return_(&[]);
+                self.builder.seal_block(header_block);
+
+                self.builder.switch_to_block(exit_block);
+                self.builder.seal_block(exit_block);
                 ParseExpr::empty()
             }
-            ExprType::Return(expr) => {
-                match expr {
-                    Some(expr) => {
-                        let value = self.expr(&*expr)?;
-                        self.builder.ins().return_(&[value.value.unwrap()]); // TODO: Properly unwrap this
-                        ParseExpr::new_return(value.value)
-                    }
-                    None => {
+            ExprType::DoWhile(body, condition, label) => {
+                let body_block = self.builder.create_block();
+                let condition_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+
+                self.builder.ins().jump(body_block, &[]);
+
+                self.builder.switch_to_block(body_block);
+                self.loops.push(LoopTargets {
+                    label: label.clone(),
+                    continue_block: condition_block,
+                    exit_block,
+                    exited: true,
+                });
+                let body = self.expr(body);
+                self.loops.pop();
+                if !body?.is_return {
+                    self.builder.ins().jump(condition_block, &[]);
+                }
+
+                self.builder.switch_to_block(condition_block);
+                self.builder.seal_block(condition_block);
+                let condition = self.value(condition)?;
+                self.builder
+                    .ins()
+                    .brif(condition, body_block, &[], exit_block, &[]);
+                self.builder.seal_block(body_block);
+
+                self.builder.switch_to_block(exit_block);
+                self.builder.seal_block(exit_block);
+                ParseExpr::empty()
+            }
+            ExprType::Loop(body, label) => {
+                let body_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+
+                self.builder.ins().jump(body_block, &[]);
+
+                self.builder.switch_to_block(body_block);
+                self.loops.push(LoopTargets {
+                    label: label.clone(),
+                    continue_block: body_block,
+                    exit_block,
+                    exited: false,
+                });
+                let body = self.expr(body);
+                let exited = self.loops.pop().unwrap().exited;
+                if !body?.is_return {
+                    self.builder.ins().jump(body_block, &[]);
+                }
+                self.builder.seal_block(body_block);
+
+                self.builder.switch_to_block(exit_block);
+                self.builder.seal_block(exit_block);
+                // Without a `break` nothing after the loop is reachable
+                if exited {
+                    ParseExpr::empty()
+                } else {
+                    ParseExpr::empty_return()
+                }
+            }
+            ExprType::Break(label) => {
+                let targets = self.loop_targets(label);
+                targets.exited = true;
+                let exit_block = targets.exit_block;
+                self.builder.ins().jump(exit_block, &[]);
+                ParseExpr::empty_return()
+            }
+            ExprType::Continue(label) => {
+                let continue_block = self.loop_targets(label).continue_block;
+                self.builder.ins().jump(continue_block, &[]);
+                ParseExpr::empty_return()
+            }
+            ExprType::Return(Some(call)) if self.is_tail_call(call) => {
+                let ExprType::Call(_, args) = &call.expr_type else {
+                    unreachable!()
+                };
+                self.tail_call(args)?
+            }
+            ExprType::Return(expr) => match expr {
+                Some(expr) if self.return_type == AuroraType::Void => {
+                    return Err(Error::UnexpectedReturnValue(expr.type_.clone()));
+                }
+                Some(expr) => {
+                    let value = self.return_value(expr)?;
+                    if let Some(address) = self.struct_return {
+                        let return_type = self.return_type.clone();
+                        self.store(value, &return_type, address, 0);
                         self.builder.ins().return_(&[]);
-                        ParseExpr::empty_return()
+                    } else {
+                        self.builder.ins().return_(&[value]);
                     }
+                    ParseExpr::new_return(Some(value))
                 }
-            }
-            ExprType::Let(name, int_expr) => match int_expr {
+                None if self.return_type != AuroraType::Void => {
+                    return Err(Error::MissingReturnValue(self.return_type.clone()));
+                }
+                None => {
+                    self.builder.ins().return_(&[]);
+                    ParseExpr::empty_return()
+                }
+            },
+            ExprType::Let(name, mutable, int_expr) => match int_expr {
                 None => {
-                    let variable = self
-                        .variable_builder
-                        .define_var(&mut self.builder, get_type_from_str(&expr.type_).unwrap());
-                    self.values.insert(name.clone(), variable);
+                    let pointer_type = self.pointer_type();
+                    let type_ = expr.type_.get_type(pointer_type).unwrap();
+                    let local = if expr.type_.is_aggregate() {
+                        let address = self.stack_slot(expr.type_.size(pointer_type));
+                        let variable =
+                            self.variable_builder
+                                .create_var(&mut self.builder, address, type_);
+                        Local {
+                            variable,
+                            mutable: *mutable,
+                            initialized: true,
+                            assigned: true,
+                            spilled: false,
+                            loop_depth: self.loops.len(),
+                        }
+                    } else if self.address_taken.contains(name) {
+                        let address = self.stack_slot(type_.bytes());
+                        let variable = self.variable_builder.create_var(
+                            &mut self.builder,
+                            address,
+                            pointer_type,
+                        );
+                        Local {
+                            variable,
+                            mutable: *mutable,
+                            initialized: false,
+                            assigned: false,
+                            spilled: true,
+                            loop_depth: self.loops.len(),
+                        }
+                    } else {
+                        let variable = self.variable_builder.define_var(&mut self.builder, type_);
+                        Local {
+                            variable,
+                            mutable: *mutable,
+                            initialized: false,
+                            assigned: false,
+                            spilled: false,
+                            loop_depth: self.loops.len(),
+                        }
+                    };
+                    self.declare(*name, local);
                     ParseExpr::empty()
                 }
                 Some(value) => {
-                    let parse_expr = self.expr(&*value)?;
-                    let variable = self.variable_builder.create_var(
-                        &mut self.builder,
-                        parse_expr.value.expect("value"),
-                        get_type_from_str(&expr.type_).unwrap(),
+                    let initial = self.value(value)?;
+                    let pointer_type = self.pointer_type();
+                    let type_ = expr.type_.get_type(pointer_type).unwrap();
+                    let mut value = initial;
+                    let spilled = self.address_taken.contains(name) && !expr.type_.is_aggregate();
+                    let variable = if spilled {
+                        let address = self.stack_slot(type_.bytes());
+                        self.builder
+                            .ins()
+                            .store(MemFlags::trusted(), value, address, 0);
+                        value = address;
+                        self.variable_builder
+                            .create_var(&mut self.builder, value, pointer_type)
+                    } else if expr.type_.is_aggregate() {
+                        // The value is the address of the aggregate it's initialized from,
+                        // which the binding gets a copy of
+                        let address = self.stack_slot(expr.type_.size(pointer_type));
+                        self.store(value, &expr.type_, address, 0);
+                        self.variable_builder
+                            .create_var(&mut self.builder, address, type_)
+                    } else {
+                        self.variable_builder
+                            .create_var(&mut self.builder, value, type_)
+                    };
+                    self.declare(
+                        *name,
+                        Local {
+                            variable,
+                            mutable: *mutable,
+                            initialized: true,
+                            assigned: true,
+                            spilled,
+                            loop_depth: self.loops.len(),
+                        },
                     );
-                    self.values.insert(name.clone(), variable);
-                    parse_expr
+                    ParseExpr::new(Some(initial))
                 }
             },
             ExprType::Assign(name, value) => {
-                let val = self.expr(&*value)?;
-                let var = self.values.get(name);
+                let type_ = value.type_.clone();
+                let value = self.value(value)?;
+                let loop_depth = self.loops.len();
+                let var = self.lookup_mut(*name);
                 match var {
-                    Some(variable) => {
-                        self.builder.def_var(*variable, val.value.unwrap());
-                        val
+                    Some(local) if !local.mutable && local.initialized => {
+                        return Err(Error::AssignToImmutable(name.to_string()))
+                    }
+                    Some(local) if !local.mutable && loop_depth > local.loop_depth => {
+                        return Err(Error::AssignToImmutable(name.to_string()))
+                    }
+                    Some(local) => {
+                        local.initialized = true;
+                        local.assigned = true;
+                        let local = *local;
+                        if local.spilled {
+                            let address = self.builder.use_var(local.variable);
+                            self.builder
+                                .ins()
+                                .store(MemFlags::trusted(), value, address, 0);
+                        } else if type_.is_aggregate() {
+                            // Copied into the binding's own memory
+                            let address = self.builder.use_var(local.variable);
+                            self.store(value, &type_, address, 0);
+                        } else {
+                            self.builder.def_var(local.variable, value);
+                        }
+                        ParseExpr::new(Some(value))
                     }
                     None => return Err(Error::Undefined(format!("variable {}", name))),
                 }
@@ -259,27 +1416,91 @@ impl<'a> FunctionGenerator<'a> {
 }
 
 impl Generator {
-    pub fn new() -> Self {
-        let shared_builder = settings::builder();
-        // shared_builder
-        //     .set("opt_level", "best")
-        //     .expect("set optlevel");
+    fn pointer_type(&self) -> Type {
+        self.module.target_config().pointer_type()
+    }
+
+    pub fn new(options: &CompileOptions) -> Result<Self> {
+        let mut shared_builder = settings::builder();
+        shared_builder
+            .set("opt_level", options.opt_level.as_str())
+            .expect("set optlevel");
+        // Passing i128 values in registers follows the LLVM convention, off by default
+        shared_builder
+            .enable("enable_llvm_abi_extensions")
+            .expect("enable llvm abi extensions");
+        // cc links a position independent executable by default, which calls through absolute
+        // relocations in `.text` would have to be patched in at load time
+        shared_builder.enable("is_pic").expect("enable pic");
         let shared_flags = settings::Flags::new(shared_builder);
-        let isa_builder = isa::lookup(triple!("x86_64-unknown-linux-gnu")).expect("isa");
-        let isa = isa_builder.finish(shared_flags).expect("Isa error");
+        let isa_builder = Triple::from_str(&options.target)
+            .ok()
+            .and_then(|triple| isa::lookup(triple).ok())
+            .ok_or_else(|| Error::UnsupportedTarget(options.target.clone()))?;
+        let isa = isa_builder.finish(shared_flags)?;
 
-        let builder = ObjectBuilder::new(isa, "program", default_libcall_names()).unwrap(); //TODO: Fix tehse unwraps
+        let builder = ObjectBuilder::new(isa, "program", default_libcall_names())?;
         let module = ObjectModule::new(builder);
-        Self {
+        Ok(Self {
             builder_context: FunctionBuilderContext::new(),
             functions: HashMap::new(),
             module,
             variable_builder: VariableBuilder::new(),
+            overflow: options.overflow,
+            float_compare: options.float_compare,
+            checked_div: options.checked_div,
+            print_ast: options.print_ast,
+            emit_ir: options.emit_ir,
+            emit_asm: options.emit_asm,
+            explain_types: options.explain_types,
+            debug: options
+                .debug_info
+                .then(|| DebugInfo::new(&options.source_name)),
+            times: PassTimes::default(),
+        })
+    }
+
+    // Whether a function of this name has a body in the module
+    pub fn is_defined(&self, name: Symbol) -> bool {
+        self.functions
+            .get(&name)
+            .is_some_and(|function| function.defined)
+    }
+
+    // Lists every declared function, sorted by name, with its linkage and signature
+    pub fn print_symbols(&self) {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by_key(|(name, _)| name.as_str());
+        for (name, function) in functions {
+            let linkage = self
+                .module
+                .declarations()
+                .get_function_decl(function.id)
+                .linkage;
+            println!(
+                "{:<8} {} ({} params) -> {}",
+                format!("{:?}", linkage),
+                name,
+                function.signature.param_types.len(),
+                function.signature.return_type,
+            );
+        }
+    }
+
+    // Finishes the module into the bytes of an object file
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let mut product = self.module.finish();
+        if let Some(debug) = &self.debug {
+            debug.write(&mut product)?;
         }
+        Ok(product.emit().unwrap())
     }
 
     pub fn init_essential_lib(&mut self) -> Result<()> {
         self.raw_func()?;
+        for prototype in runtime::prototypes() {
+            self.prototype(&prototype, Linkage::Import)?;
+        }
         Ok(())
     }
 
@@ -293,40 +1514,54 @@ impl Generator {
     //     }
     // }
 
-    fn signature_append_from_prototype(&self, prototype: &Prototype, signature: &mut Signature) {
+    // Arrays and structs are returned by copying them to memory of the caller, whose address
+    // is passed before the other arguments
+    fn signature_append_from_prototype(
+        &self,
+        prototype: &Prototype,
+        signature: &mut Signature,
+    ) -> Result<()> {
+        if prototype.return_type.is_aggregate() {
+            signature.params.push(AbiParam::special(
+                self.pointer_type(),
+                ArgumentPurpose::StructReturn,
+            ));
+        }
         for parameter in &prototype.parameters {
-            let type_ = get_type_from_str(&parameter.type_).expect("Parameter can't be void");
+            let type_ = parameter
+                .type_
+                .get_type(self.pointer_type())
+                .ok_or_else(|| Error::VoidParameter(parameter.name.to_string()))?;
             signature.params.push(AbiParam::new(type_));
         }
 
-        let return_type = get_type_from_str(&prototype.return_type);
-        if let Some(tp) = return_type {
-            signature.returns.push(AbiParam::new(tp));
+        let return_type = prototype.return_type.get_type(self.pointer_type());
+        match return_type {
+            Some(tp) if !prototype.return_type.is_aggregate() => {
+                signature.returns.push(AbiParam::new(tp))
+            }
+            _ => (),
         }
+        Ok(())
     }
 
     pub fn prototype(&mut self, prototype: &Prototype, linkage: Linkage) -> Result<FuncId> {
-        let function_name = &prototype.function_name;
+        let function_name = prototype.function_name;
 
-        match self.functions.get(function_name) {
+        match self.functions.get(&function_name) {
             None => {
                 let mut signature = self.module.make_signature();
-                self.signature_append_from_prototype(prototype, &mut signature);
+                self.signature_append_from_prototype(prototype, &mut signature)?;
 
-                let id = self
-                    .module
-                    .declare_function(&function_name, linkage, &signature)?;
+                let id =
+                    self.module
+                        .declare_function(function_name.as_str(), linkage, &signature)?;
                 self.functions.insert(
-                    function_name.to_string(),
+                    function_name,
                     CompiledFunction {
                         defined: false,
                         id,
-                        param_count: prototype.parameters.len(),
-                        return_count: if prototype.return_type == "void" {
-                            0
-                        } else {
-                            1
-                        },
+                        signature: typing::Signature::new(prototype, linkage == Linkage::Import),
                     },
                 );
                 Ok(id)
@@ -335,7 +1570,7 @@ impl Generator {
                 if function.defined {
                     return Err(Error::FunctionRedef);
                 }
-                if function.param_count != prototype.parameters.len() {
+                if function.signature.param_types.len() != prototype.parameters.len() {
                     return Err(Error::FunctionRedefWithDifferentParams);
                 }
                 Ok(function.id)
@@ -345,25 +1580,26 @@ impl Generator {
 
     pub fn raw_func(&mut self) -> Result<()> {
         macro_rules! decl_cast {
-            ($from:literal,$to:literal,$exec:block) => {
+            ($from:expr,$to:expr,$exec:block) => {
                 let mut context = self.module.make_context();
                 let signature = &mut context.func.signature;
                 signature
                     .params
-                    .push(AbiParam::new(typing::get_type_from_str($from).unwrap()));
+                    .push(AbiParam::new($from.get_type(self.pointer_type()).unwrap()));
                 signature
                     .returns
-                    .push(AbiParam::new(typing::get_type_from_str($to).unwrap()));
+                    .push(AbiParam::new($to.get_type(self.pointer_type()).unwrap()));
 
                 let parameters = vec![Parameter {
-                    name: "val".to_string(),
+                    name: Symbol::intern("val"),
                     type_: $from,
                 }];
 
                 let prototype = Prototype {
-                    function_name: format!("{}->{}", $from, $to),
+                    function_name: Symbol::intern(&format!("{}->{}", $from, $to)),
                     parameters,
                     return_type: $to,
+                    variadic: false,
                 };
 
                 let func_id = self.prototype(&prototype, Linkage::Export)?;
@@ -383,11 +1619,14 @@ impl Generator {
 
                 builder.ins().return_(&[return_value]);
 
-                if let Some(ref mut function) = self.functions.get_mut("i64->f64") {
+                if let Some(ref mut function) = self.functions.get_mut(&Symbol::intern("i64->f64"))
+                {
                     function.defined = true;
                 }
                 builder.finalize();
-                println!("{}", context.func.display().to_string());
+                if self.emit_ir {
+                    println!("{}", context.func.display());
+                }
 
                 self.module.define_function(func_id, &mut context)?;
                 self.module.clear_context(&mut context);
@@ -395,50 +1634,50 @@ impl Generator {
         }
 
         // Int -> Float
-        decl_cast!("i8", "f32", {
+        decl_cast!(AuroraType::I8, AuroraType::F32, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F32, *val)
             }
         });
 
-        decl_cast!("i16", "f32", {
+        decl_cast!(AuroraType::I16, AuroraType::F32, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F32, *val)
             }
         });
 
-        decl_cast!("i32", "f32", {
+        decl_cast!(AuroraType::I32, AuroraType::F32, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F32, *val)
             }
         });
 
-        decl_cast!("i64", "f32", {
+        decl_cast!(AuroraType::I64, AuroraType::F32, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F32, *val)
             }
         });
 
         // Int -> Double
-        decl_cast!("i8", "f64", {
+        decl_cast!(AuroraType::I8, AuroraType::F64, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F64, *val)
             }
         });
 
-        decl_cast!("i16", "f64", {
+        decl_cast!(AuroraType::I16, AuroraType::F64, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F64, *val)
             }
         });
 
-        decl_cast!("i32", "f64", {
+        decl_cast!(AuroraType::I32, AuroraType::F64, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F64, *val)
             }
         });
 
-        decl_cast!("i64", "f64", {
+        decl_cast!(AuroraType::I64, AuroraType::F64, {
             |builder: &mut FunctionBuilder, val: &Value| {
                 builder.ins().fcvt_from_sint(types::F64, *val)
             }
@@ -447,15 +1686,32 @@ impl Generator {
         Ok(())
     }
 
-    pub fn function(&mut self, function: Function) -> Result<()> {
+    pub fn function(&mut self, mut function: Function) -> Result<()> {
+        if self.print_ast {
+            println!("{:#?}", function);
+        }
+        let functions = &self.functions;
+        self.times.time("type checking", || {
+            typing::check(&mut function, |name| {
+                functions.get(&name).map(|function| &function.signature)
+            })
+        })?;
+        if self.explain_types {
+            typing::explain(&function);
+        }
+        let start = Instant::now();
+        opt::propagate_constants(&mut function.body);
+        opt::fold_constants(&mut function.body, self.float_compare);
+
         let mut context = self.module.make_context();
-        let mut signature = &mut context.func.signature;
+        let signature = &mut context.func.signature;
         let parameters = &function.prototype.parameters;
 
-        self.signature_append_from_prototype(&function.prototype, &mut signature);
+        self.signature_append_from_prototype(&function.prototype, signature)?;
 
-        let function_name = function.prototype.function_name.to_string();
+        let function_name = function.prototype.function_name;
         let func_id = self.prototype(&function.prototype, Linkage::Export)?;
+        let pointer_type = self.pointer_type();
 
         // Creates new block for function
         let mut builder = FunctionBuilder::new(&mut context.func, &mut self.builder_context);
@@ -463,48 +1719,231 @@ impl Generator {
         builder.append_block_params_for_function_params(entry_block);
         builder.switch_to_block(entry_block);
         builder.seal_block(entry_block);
+        if self.debug.is_some() {
+            builder.set_srcloc(SourceLoc::new(function.line as u32));
+        }
+
+        let mut address_taken = HashSet::new();
+        collect_address_taken(&function.body, &mut address_taken);
+
+        // The address to return an array or struct at comes before the parameters
+        let struct_return = function
+            .prototype
+            .return_type
+            .is_aggregate()
+            .then(|| builder.block_params(entry_block)[0]);
+        let first_param = usize::from(struct_return.is_some());
 
         // Add parameters to stack
         let mut values = HashMap::new();
+        let mut param_variables = vec![];
         for (i, parameter) in parameters.iter().enumerate() {
-            let val = builder.block_params(entry_block)[i];
-            let variable = self.variable_builder.create_var(
-                &mut builder,
-                val,
-                get_type_from_str(&parameter.type_).unwrap(), // Safe to unwrap, it would've panicked while making the prototype otherwise
+            let mut val = builder.block_params(entry_block)[first_param + i];
+            // Safe to unwrap, it would've panicked while making the prototype otherwise
+            let mut type_ = parameter.type_.get_type(pointer_type).unwrap();
+            let spilled =
+                address_taken.contains(&parameter.name) && !parameter.type_.is_aggregate();
+            if spilled {
+                let data = StackSlotData::new(StackSlotKind::ExplicitSlot, type_.bytes());
+                let slot = builder.create_sized_stack_slot(data);
+                let address = builder.ins().stack_addr(pointer_type, slot, 0);
+                builder.ins().store(MemFlags::trusted(), val, address, 0);
+                (val, type_) = (address, pointer_type);
+            }
+            let variable = self.variable_builder.create_var(&mut builder, val, type_);
+            param_variables.push(variable);
+            values.insert(
+                parameter.name,
+                Local {
+                    variable,
+                    mutable: false,
+                    initialized: true,
+                    assigned: true,
+                    spilled,
+                    loop_depth: 0,
+                },
             );
-            values.insert(parameter.name.clone(), variable);
         }
 
         if let Some(ref mut function) = self.functions.get_mut(&function_name) {
             function.defined = true;
         }
 
+        // The frame can't be reused once pointers into it may be passed along
+        let tail_call = (address_taken.is_empty()
+            && has_self_tail_call(&function.body, function_name))
+        .then(|| {
+            let block = builder.create_block();
+            builder.ins().jump(block, &[]);
+            builder.switch_to_block(block);
+            TailCall {
+                name: function_name,
+                block,
+                params: param_variables,
+            }
+        });
+        let tail_block = tail_call.as_ref().map(|tail_call| tail_call.block);
+
         let mut generator = FunctionGenerator {
             builder,
             functions: &self.functions,
             module: &mut self.module,
-            values,
+            values: vec![values],
+            address_taken,
+            tail_call,
+            loops: vec![],
             variable_builder: &mut self.variable_builder,
+            return_type: function.prototype.return_type.clone(),
+            struct_return,
+            overflow: self.overflow,
+            float_compare: self.float_compare,
+            checked_div: self.checked_div,
         };
 
-        match generator.expr(&function.body) {
+        let ExprType::Block(body) = &function.body.expr_type else {
+            unreachable!()
+        };
+        match generator.block(body, true) {
             Ok(value) => value,
             Err(error) => {
-                dbg!(&error);
-                generator.builder.finalize();
-                self.functions.remove(&function_name);
+                // Blocks may be left unterminated, so the builder state is discarded
+                // instead of finalized
+                self.builder_context = FunctionBuilderContext::new();
+                // The declaration stays so calls to it don't report it as undefined
+                if let Some(function) = self.functions.get_mut(&function_name) {
+                    function.defined = false;
+                }
                 return Err(error);
             }
         };
 
+        // Only now are all the jumps back to the start known
+        if let Some(block) = tail_block {
+            generator.builder.seal_block(block);
+        }
         generator.builder.finalize();
         // optimize(&mut context, self.module.isa().to_owned());
-        println!("{}", context.func.display().to_string());
+        if self.emit_ir {
+            println!("{}", context.func.display());
+        }
+        // Cranelift keeps the text of the machine code it generates when asked before
+        context.set_disasm(self.emit_asm);
 
         self.module.define_function(func_id, &mut context)?;
+        if self.emit_asm {
+            let code = context.compiled_code().unwrap();
+            if let Some(asm) = &code.vcode {
+                println!("{}:\n{}", function_name, asm);
+            }
+        }
+        if let Some(debug) = &mut self.debug {
+            let code = context.compiled_code().unwrap();
+            debug.add_function(func_id, function_name.as_str(), function.line, code);
+        }
         self.module.clear_context(&mut context);
         // self.module.finalize_definitions();
+        self.times.add("codegen", start.elapsed());
         Ok(())
     }
 }
+
+fn void_value(expr: &Expr) -> Error {
+    match &expr.expr_type {
+        ExprType::Call(name, _) => Error::VoidValueUsed(name.to_string()),
+        _ => Error::Unexpected("expression without a value"),
+    }
+}
+
+// Collects the names of the variables whose address is taken, which have to live on the
+// stack instead of in registers
+fn collect_address_taken(expr: &Expr, names: &mut HashSet<Symbol>) {
+    match &expr.expr_type {
+        ExprType::AddressOf(place) => {
+            if let ExprType::Variable(name) = &place.expr_type {
+                names.insert(*name);
+            }
+            collect_address_taken(place, names);
+        }
+        ExprType::Binary(_, left, right) | ExprType::Intrinsic(_, left, right) => {
+            collect_address_taken(left, names);
+            collect_address_taken(right, names);
+        }
+        ExprType::Call(_, args) => args
+            .iter()
+            .for_each(|arg| collect_address_taken(&arg.value, names)),
+        ExprType::Block(exprs) => exprs
+            .iter()
+            .for_each(|expr| collect_address_taken(expr, names)),
+        ExprType::Let(_, _, Some(value))
+        | ExprType::Assign(_, value)
+        | ExprType::Return(Some(value))
+        | ExprType::Deref(value)
+        | ExprType::Field(value, _)
+        | ExprType::Unary(_, value)
+        | ExprType::Cast(value)
+        | ExprType::Assert(value, _)
+        | ExprType::Loop(value, _) => collect_address_taken(value, names),
+        ExprType::Struct(values) => values
+            .iter()
+            .for_each(|(_, value)| collect_address_taken(value, names)),
+        ExprType::IfElse(condition, then, else_) => {
+            collect_address_taken(condition, names);
+            collect_address_taken(then, names);
+            if let Some(else_) = else_ {
+                collect_address_taken(else_, names);
+            }
+        }
+        ExprType::Match(value, arms, default) => {
+            collect_address_taken(value, names);
+            arms.iter()
+                .for_each(|(_, body)| collect_address_taken(body, names));
+            collect_address_taken(default, names);
+        }
+        ExprType::While(left, right, _)
+        | ExprType::DoWhile(left, right, _)
+        | ExprType::Index(left, right)
+        | ExprType::AssignDeref(left, right) => {
+            collect_address_taken(left, names);
+            collect_address_taken(right, names);
+        }
+        ExprType::AssignIndex(array, index, value) => {
+            collect_address_taken(array, names);
+            collect_address_taken(index, names);
+            collect_address_taken(value, names);
+        }
+        ExprType::Integer(_)
+        | ExprType::Float(_)
+        | ExprType::Boolean(_)
+        | ExprType::Str(_)
+        | ExprType::Variable(_)
+        | ExprType::SizeOf(_)
+        | ExprType::Let(_, _, None)
+        | ExprType::Break(_)
+        | ExprType::Continue(_)
+        | ExprType::Return(None) => {}
+    }
+}
+
+// Whether the function returns a call to itself anywhere in its body
+fn has_self_tail_call(expr: &Expr, name: Symbol) -> bool {
+    match &expr.expr_type {
+        ExprType::Return(Some(value)) => {
+            matches!(&value.expr_type, ExprType::Call(callee, _) if *callee == name)
+        }
+        ExprType::Block(exprs) => exprs.iter().any(|expr| has_self_tail_call(expr, name)),
+        ExprType::IfElse(_, then, else_) => {
+            has_self_tail_call(then, name)
+                || else_
+                    .as_ref()
+                    .is_some_and(|else_| has_self_tail_call(else_, name))
+        }
+        ExprType::While(_, body, _) | ExprType::DoWhile(body, _, _) | ExprType::Loop(body, _) => {
+            has_self_tail_call(body, name)
+        }
+        ExprType::Match(_, arms, default) => {
+            arms.iter().any(|(_, body)| has_self_tail_call(body, name))
+                || has_self_tail_call(default, name)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+};
+
+// An interned identifier. The same name always gets the same symbol, so comparing and
+// hashing one is as cheap as for an integer
+// Not ordered, as their order would be the one they were interned in rather than by name.
+// Neither `Send` nor `Sync`, the interner giving them a meaning being per thread
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        INTERNER.with(|interner| interner.borrow_mut().intern(name))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().resolve(self))
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+// Shown as the name it stands for, so dumps of tokens and syntax trees stay readable
+impl Debug for Symbol {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        Debug::fmt(self.as_str(), formatter)
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// Names of every symbol interned so far. Names are leaked, they live as long as the
+// compiler does anyway
+#[derive(Default)]
+pub struct Interner {
+    names: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(self.names.len() as u32, PhantomData);
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &'static str {
+        self.names[symbol.0 as usize]
+    }
+}
+
+thread_local! {
+    // Symbols are only meaningful on the thread that interned them
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
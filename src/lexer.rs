@@ -1,27 +1,42 @@
 use std::{
     collections::VecDeque,
     fmt::Display,
-    io::{Bytes, Read},
+    io::{BufReader, Bytes, Cursor, Read},
     iter::Peekable,
 };
 
-use crate::error::Error::UnknownChar;
+use crate::error::Error::{
+    InvalidEscape, MalformedNumber, NonUtf8String, NumberOutOfRange, Positioned, UnknownChar,
+    UnterminatedString,
+};
 use crate::error::Result;
+use crate::intern::Symbol;
 
 pub struct Lexer<R: Read> {
-    bytes: Peekable<Bytes<R>>,
-    lookahead: VecDeque<Token>,
+    bytes: Peekable<Bytes<BufReader<R>>>,
+    lookahead: VecDeque<Spanned<Token>>,
     line: usize,
     pos: usize,
+    // Line and column right after the last token handed out
+    last_end: (usize, usize),
+}
+
+impl Lexer<Cursor<Vec<u8>>> {
+    // Lexes a source held in memory
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &str) -> Self {
+        Self::new(Cursor::new(source.as_bytes().to_vec()))
+    }
 }
 
 impl<R: Read> Lexer<R> {
     pub fn new(reader: R) -> Self {
         Self {
-            bytes: reader.bytes().peekable(),
+            bytes: BufReader::new(reader).bytes().peekable(),
             lookahead: VecDeque::new(),
             line: 1,
             pos: 1,
+            last_end: (1, 1),
         }
     }
 
@@ -34,46 +49,156 @@ impl<R: Read> Lexer<R> {
     }
 
     pub fn next_token(&mut self) -> Result<Token> {
-        if let Some(lookahead) = self.lookahead.pop_front() {
-            return Ok(lookahead);
+        self.next_spanned().map(|spanned| spanned.value)
+    }
+
+    pub fn next_spanned(&mut self) -> Result<Spanned<Token>> {
+        let spanned = match self.lookahead.pop_front() {
+            Some(lookahead) => lookahead,
+            None => self.next_spanned_no_check()?,
+        };
+        self.last_end = (spanned.span.end_line, spanned.span.end_col);
+        Ok(spanned)
+    }
+
+    // From the start of a span to the end of the last token handed out
+    pub fn span_from(&self, start: Span) -> Span {
+        let (end_line, end_col) = self.last_end;
+        Span {
+            end_line,
+            end_col,
+            ..start
         }
+    }
 
-        self.next_token_no_check()
+    // Input that fails to lex is still consumed, so lexing can resume after an error
+    fn next_spanned_no_check(&mut self) -> Result<Spanned<Token>> {
+        self.skip_trivia()?;
+        let (start_line, start_col) = (self.line, self.pos);
+        // Reported where the token starts rather than where lexing stopped
+        let value = self
+            .token()
+            .map_err(|error| Positioned(start_line, start_col, Box::new(error)))?;
+        Ok(Spanned {
+            value,
+            span: Span {
+                start_line,
+                start_col,
+                end_line: self.line,
+                end_col: self.pos,
+            },
+        })
     }
 
-    fn next_token_no_check(&mut self) -> Result<Token> {
+    // Skips whitespace and comments. A comment runs until the end of the line or of the
+    // source, and may directly follow a token as in `3# three`
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            match self.peek_char()? {
+                Some(' ' | '\r' | '\t' | '\n') => self.bump(),
+                Some('#') => {
+                    while !matches!(self.peek_char()?, Some('\n') | None) {
+                        self.bump();
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // Consumes a byte, keeping track of the position in the source
+    fn bump(&mut self) {
+        if let Some(Ok(byte)) = self.bytes.next() {
+            if byte == b'\n' {
+                self.line += 1;
+                self.pos = 1;
+            } else {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<Token> {
         if let Some(&Ok(byte)) = self.bytes.peek() {
-            self.pos += 1;
             return match byte {
-                // Skips empty spaces
-                b' ' | b'\r' | b'\t' => {
-                    self.bytes.next();
-                    self.next_token_no_check()
-                }
-                b'\n' => {
-                    self.line += 1;
-                    self.pos = 1;
-                    self.bytes.next();
-                    self.next_token_no_check()
-                }
                 b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.identifier(),
-                b'0'..=b'9' | b'.' => self.number(),
-                b'#' => self.comment(),
+                b'0'..=b'9' => self.number(),
+                b'.' => self.dot(),
+                b'"' => self.string(),
+                b'\'' => self.label(),
                 _ => {
-                    self.bytes.next();
+                    self.bump();
                     let token = match byte {
-                        b'<' => Token::LessThan,
+                        b'<' => match self.bytes.peek() {
+                            Some(Ok(b'=')) => {
+                                self.bump();
+                                Token::LessEqual
+                            }
+                            Some(Ok(b'<')) => {
+                                self.bump();
+                                Token::ShiftLeft
+                            }
+                            _ => Token::LessThan,
+                        },
+                        b'>' => match self.bytes.peek() {
+                            Some(Ok(b'=')) => {
+                                self.bump();
+                                Token::GreaterEqual
+                            }
+                            Some(Ok(b'>')) => {
+                                self.bump();
+                                Token::ShiftRight
+                            }
+                            _ => Token::GreaterThan,
+                        },
                         b'+' => Token::Plus,
                         b'-' => Token::Minus,
                         b'*' => Token::Star,
+                        b'/' => Token::Slash,
+                        b'%' => Token::Percent,
+                        b'^' => Token::Caret,
+                        b'!' => match self.bytes.peek() {
+                            Some(Ok(b'=')) => {
+                                self.bump();
+                                Token::BangEqual
+                            }
+                            _ => return Err(UnknownChar('!')),
+                        },
+                        b'~' => Token::Tilde,
                         b';' => Token::SemiColon,
                         b',' => Token::Comma,
                         b'(' => Token::OpenParen,
                         b')' => Token::CloseParen,
                         b'{' => Token::OpenBracket,
                         b'}' => Token::CloseBracket,
-                        b'=' => Token::Equal,
+                        b'[' => Token::OpenSquare,
+                        b']' => Token::CloseSquare,
+                        b'=' => match self.bytes.peek() {
+                            Some(Ok(b'=')) => {
+                                self.bump();
+                                Token::EqualEqual
+                            }
+                            Some(Ok(b'>')) => {
+                                self.bump();
+                                Token::FatArrow
+                            }
+                            _ => Token::Equal,
+                        },
                         b':' => Token::Colon,
+                        b'&' => match self.bytes.peek() {
+                            Some(Ok(b'&')) => {
+                                self.bump();
+                                Token::AndAnd
+                            }
+                            _ => Token::Ampersand,
+                        },
+                        b'|' => match self.bytes.peek() {
+                            Some(Ok(b'|')) => {
+                                self.bump();
+                                Token::OrOr
+                            }
+                            _ => Token::Pipe,
+                        },
                         _ => return Err(UnknownChar(byte as char)),
                     };
 
@@ -85,44 +210,69 @@ impl<R: Read> Lexer<R> {
     }
 
     pub fn peek(&mut self, dist: usize) -> Result<&Token> {
+        self.peek_spanned(dist).map(|spanned| &spanned.value)
+    }
+
+    pub fn peek_spanned(&mut self, dist: usize) -> Result<&Spanned<Token>> {
         loop {
-            if self.lookahead.len() >= dist + 1 {
+            if self.lookahead.len() > dist {
                 return Ok(self.lookahead.get(dist).unwrap());
             }
 
-            let token = self.next_token_no_check()?;
+            let token = self.next_spanned_no_check()?;
             self.lookahead.push_back(token);
         }
     }
 
     fn identifier(&mut self) -> Result<Token> {
-        let mut identifier = String::new();
-        loop {
-            if let Some(char) = self.peek_char()? {
-                if char.is_ascii_alphanumeric() || char == '_' {
-                    self.bytes.next();
-                    identifier.push(char);
-                    continue;
-                }
-            }
-            break;
+        let identifier = self.word()?;
+        if identifier == "r" && self.peek_char()? == Some('"') {
+            return self.raw_string();
         }
 
         let token = match identifier.as_str() {
             "fn" => Token::Def,
             "extern" => Token::Extern,
+            "import" => Token::Import,
+            "struct" => Token::Struct,
+            "enum" => Token::Enum,
             "return" => Token::Return,
             "let" => Token::Let,
-            _ => Token::Identifier(identifier),
+            "mut" => Token::Mut,
+            "if" => Token::If,
+            "else" => Token::Else,
+            "while" => Token::While,
+            "do" => Token::Do,
+            "as" => Token::As,
+            "match" => Token::Match,
+            "loop" => Token::Loop,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
+            "true" => Token::True,
+            "false" => Token::False,
+            _ => Token::Identifier(Symbol::intern(&identifier)),
         };
 
         Ok(token)
     }
 
+    // `'name`, naming the loop it comes before
+    fn label(&mut self) -> Result<Token> {
+        self.bump();
+        let name = self.word()?;
+        if name.is_empty() || name.starts_with(|char: char| char.is_ascii_digit()) {
+            return Err(UnknownChar('\''));
+        }
+        Ok(Token::Label(name))
+    }
+
     fn peek_char(&mut self) -> Result<Option<char>> {
+        Ok(self.peek_byte()?.map(char::from))
+    }
+
+    fn peek_byte(&mut self) -> Result<Option<u8>> {
         if let Some(&Ok(byte)) = self.bytes.peek() {
-            self.pos += 1;
-            return Ok(Some(byte as char));
+            return Ok(Some(byte));
         }
 
         match self.bytes.next() {
@@ -132,23 +282,216 @@ impl<R: Read> Lexer<R> {
         }
     }
 
+    fn word(&mut self) -> Result<String> {
+        let mut word = String::new();
+        loop {
+            if let Some(char) = self.peek_char()? {
+                if char.is_ascii_alphanumeric() || char == '_' {
+                    self.bump();
+                    word.push(char);
+                    continue;
+                }
+            }
+            break;
+        }
+        Ok(word)
+    }
+
     fn number(&mut self) -> Result<Token> {
         let integral = self.digits()?;
+        if integral == "0" && self.peek_char()? == Some('x') {
+            self.bump();
+            return self.hex_number();
+        }
         if let Some('.') = self.peek_char()? {
-            self.bytes.next();
+            self.bump();
             let decimals = self.digits()?;
             Ok(Token::Float(format!("{}.{}", integral, decimals).parse()?))
         } else {
-            Ok(Token::Integer(integral.parse()?))
+            // Optional type suffix, e.g. `5i32`
+            let suffix = match self.peek_char()? {
+                Some(char) if char.is_ascii_alphabetic() => Some(self.word()?),
+                _ => None,
+            };
+            let value = integral
+                .parse()
+                .map_err(|_| NumberOutOfRange(integral.clone()))?;
+            Ok(Token::Integer(value, suffix))
+        }
+    }
+
+    // What follows `0x`, an integer or a float with a binary exponent as in `0x1.8p3`. Float
+    // digits past the 53 bits of an `f64` are rounded
+    fn hex_number(&mut self) -> Result<Token> {
+        let integral = self.hex_digits()?;
+        let fraction = match self.peek_char()? {
+            Some('.') => {
+                self.bump();
+                Some(self.hex_digits()?)
+            }
+            _ => None,
+        };
+        let exponent = self.peek_char()? == Some('p');
+        if fraction.is_none() && !exponent && !integral.is_empty() {
+            let suffix = match self.peek_char()? {
+                Some(char) if char.is_ascii_alphabetic() => Some(self.word()?),
+                _ => None,
+            };
+            let value = u128::from_str_radix(&integral, 16)
+                .map_err(|_| NumberOutOfRange(format!("0x{}", integral)))?;
+            return Ok(Token::Integer(value, suffix));
+        }
+
+        if !exponent || (integral.is_empty() && fraction.as_ref().is_none_or(String::is_empty)) {
+            let fraction = fraction.map(|fraction| format!(".{}", fraction));
+            let literal = format!("0x{}{}", integral, fraction.unwrap_or_default());
+            return Err(MalformedNumber(literal));
+        }
+        let fraction = fraction.unwrap_or_default();
+        self.bump();
+        let sign = match self.peek_char()? {
+            Some('-') => {
+                self.bump();
+                -1
+            }
+            Some('+') => {
+                self.bump();
+                1
+            }
+            _ => 1,
+        };
+        let digits = self.digits()?;
+        let exponent: i32 = digits
+            .parse()
+            .map_err(|_| MalformedNumber(format!("0x{}.{}p{}", integral, fraction, digits)))?;
+        let mantissa = integral
+            .chars()
+            .chain(fraction.chars())
+            .fold(0.0, |value, digit| {
+                value * 16.0 + digit.to_digit(16).unwrap() as f64
+            });
+        // Saturates rather than overflowing on absurd exponents, which make 0 or inf anyway
+        let shift = i32::try_from(4 * fraction.len()).unwrap_or(i32::MAX);
+        let exponent = (sign * exponent).saturating_sub(shift);
+        Ok(Token::Float(mantissa * 2f64.powi(exponent)))
+    }
+
+    fn dot(&mut self) -> Result<Token> {
+        self.bump();
+        if let Some('.') = self.peek_char()? {
+            self.bump();
+            if let Some('.') = self.peek_char()? {
+                self.bump();
+                return Ok(Token::Ellipsis);
+            }
+            return Err(UnknownChar('.'));
+        }
+
+        let decimals = self.digits()?;
+        if decimals.is_empty() {
+            return Ok(Token::Dot);
+        }
+        Ok(Token::Float(format!("0.{}", decimals).parse()?))
+    }
+
+    // Read as bytes, which must make up UTF-8 text once escapes are replaced
+    fn string(&mut self) -> Result<Token> {
+        self.bump();
+        let mut bytes = vec![];
+        loop {
+            match self.peek_byte()? {
+                Some(b'"') => {
+                    self.bump();
+                    break;
+                }
+                Some(b'\\') => {
+                    self.bump();
+                    bytes.push(self.escape()?);
+                }
+                Some(byte) => {
+                    self.bump();
+                    bytes.push(byte);
+                }
+                None => return Err(UnterminatedString),
+            }
+        }
+
+        Ok(Token::Str(
+            String::from_utf8(bytes).map_err(|_| NonUtf8String)?,
+        ))
+    }
+
+    // What follows a `\` in a string. `\xNN` takes two hex digits of a byte
+    fn escape(&mut self) -> Result<u8> {
+        let escaped = match self.peek_char()? {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => b'\0',
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('x') => {
+                self.bump();
+                let mut digits = String::new();
+                for _ in 0..2 {
+                    match self.peek_char()? {
+                        Some(char) if char.is_ascii_hexdigit() => {
+                            self.bump();
+                            digits.push(char);
+                        }
+                        _ => break,
+                    }
+                }
+                return match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 => Ok(byte),
+                    _ => Err(InvalidEscape(format!("\\x{}", digits))),
+                };
+            }
+            Some(char) => return Err(InvalidEscape(format!("\\{}", char))),
+            None => return Err(UnterminatedString),
+        };
+        self.bump();
+        Ok(escaped)
+    }
+
+    // `r"..."`, taken as written with no escapes. Called with the `r` already consumed
+    fn raw_string(&mut self) -> Result<Token> {
+        self.bump();
+        let mut bytes = vec![];
+        loop {
+            match self.peek_byte()? {
+                Some(b'"') => {
+                    self.bump();
+                    let string = String::from_utf8(bytes).map_err(|_| NonUtf8String)?;
+                    return Ok(Token::Str(string));
+                }
+                Some(byte) => {
+                    self.bump();
+                    bytes.push(byte);
+                }
+                None => return Err(UnterminatedString),
+            }
         }
     }
 
+    fn hex_digits(&mut self) -> Result<String> {
+        let mut buffer = String::new();
+        while let Some(char) = self.peek_char()? {
+            if !char.is_ascii_hexdigit() {
+                break;
+            }
+            self.bump();
+            buffer.push(char);
+        }
+        Ok(buffer)
+    }
+
     fn digits(&mut self) -> Result<String> {
         let mut buffer = String::new();
         loop {
             if let Some(char) = self.peek_char()? {
                 if char.is_numeric() {
-                    self.bytes.next();
+                    self.bump();
                     buffer.push(char);
                     continue;
                 }
@@ -158,23 +501,36 @@ impl<R: Read> Lexer<R> {
 
         Ok(buffer)
     }
+}
 
-    fn comment(&mut self) -> Result<Token> {
-        loop {
-            if let Some(char) = self.peek_char()? {
-                self.bytes.next();
-                if char == '\n' {
-                    self.line += 1;
-                    break;
-                }
-            } else {
-                return Ok(Token::Eof);
-            }
+// Yields the remaining tokens, including the ones already peeked, stopping before `Eof`
+impl<R: Read> Iterator for Lexer<R> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(Token::Eof) => None,
+            token => Some(token),
         }
-        self.next_token()
     }
 }
 
+// Where a token or an expression was found. Lines and columns start at 1 and the end is
+// exclusive, code made up by the compiler is at line 0
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Eof,
@@ -182,20 +538,54 @@ pub enum Token {
     // Commands
     Def,
     Extern,
+    Import,
+    Struct,
+    Enum,
     Let,
+    Mut,
+    If,
+    Else,
+    While,
+    Do,
+    As,
+    Match,
+    Loop,
+    Break,
+    Continue,
 
     // Primary
-    Identifier(String),
-    Integer(i64),
+    Identifier(Symbol),
+    Label(String),
+    // The magnitude, a leading `-` being its own token
+    Integer(u128, Option<String>),
     Float(f64),
+    Str(String),
+    True,
+    False,
 
     // Operators
+    AndAnd,
+    OrOr,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
     LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
     Minus,
     Plus,
     Star,
+    Slash,
+    Percent,
 
     Equal,
+    EqualEqual,
+    BangEqual,
+    FatArrow,
 
     // Other
     Colon,
@@ -203,34 +593,77 @@ pub enum Token {
     OpenParen,
     CloseParen,
     Comma,
+    Dot,
+    Ellipsis,
     OpenBracket,
     CloseBracket,
+    OpenSquare,
+    CloseSquare,
     Return,
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Token::Eof => todo!(),
+            Token::Eof => write!(f, "<eof>"),
             Token::Def => write!(f, "fn"),
             Token::Extern => write!(f, "extern"),
+            Token::Import => write!(f, "import"),
+            Token::Struct => write!(f, "struct"),
+            Token::Enum => write!(f, "enum"),
             Token::Identifier(id) => write!(f, "identifier: {}", id),
-            Token::Float(_) => todo!(),
-            Token::Integer(_) => todo!(),
+            Token::Label(name) => write!(f, "'{}", name),
+            Token::Float(float) => write!(f, "{:?}", float),
+            Token::Integer(integer, suffix) => {
+                write!(f, "{}{}", integer, suffix.as_deref().unwrap_or(""))
+            }
+            Token::AndAnd => write!(f, "&&"),
+            Token::OrOr => write!(f, "||"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Pipe => write!(f, "|"),
+            Token::Caret => write!(f, "^"),
+            Token::Tilde => write!(f, "~"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
             Token::LessThan => write!(f, "<"),
+            Token::LessEqual => write!(f, "<="),
+            Token::GreaterThan => write!(f, ">"),
+            Token::GreaterEqual => write!(f, ">="),
             Token::Minus => write!(f, "-"),
             Token::Plus => write!(f, "+"),
             Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
             Token::SemiColon => write!(f, ";"),
             Token::OpenParen => write!(f, "("),
             Token::CloseParen => write!(f, ")"),
             Token::Comma => write!(f, ","),
+            Token::Dot => write!(f, "."),
+            Token::Ellipsis => write!(f, "..."),
+            Token::Str(string) => write!(f, "{:?}", string),
             Token::OpenBracket => write!(f, "{{"),
             Token::CloseBracket => write!(f, "}}"),
+            Token::OpenSquare => write!(f, "["),
+            Token::CloseSquare => write!(f, "]"),
             Token::Return => write!(f, "return"),
             Token::Equal => write!(f, "="),
+            Token::EqualEqual => write!(f, "=="),
+            Token::BangEqual => write!(f, "!="),
+            Token::FatArrow => write!(f, "=>"),
             Token::Let => write!(f, "let"),
+            Token::Mut => write!(f, "mut"),
             Token::Colon => write!(f, ":"),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::While => write!(f, "while"),
+            Token::Do => write!(f, "do"),
+            Token::As => write!(f, "as"),
+            Token::Match => write!(f, "match"),
+            Token::Loop => write!(f, "loop"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
         }
     }
 }
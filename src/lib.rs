@@ -0,0 +1,570 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    mem,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use cranelift_module::Linkage;
+
+use crate::{
+    ast::{ExprType, Function, Prototype},
+    error::{Diagnostic, Error, Renderer, Result, Severity, Warning},
+    gen::Generator,
+    intern::Symbol,
+    lexer::Spanned,
+    options::CompileOptions,
+    timing::PassTimes,
+};
+
+// The front end on its own, for tools that only need to read sources. Code generation
+// stays private
+pub use crate::{
+    ast::Expr,
+    lexer::{Lexer, Span, Token},
+    parser::{Item, Parser},
+    typing::AuroraType,
+};
+
+pub mod ast;
+mod debug;
+pub mod error;
+mod gen;
+pub mod intern;
+pub mod lexer;
+pub mod link;
+mod opt;
+pub mod options;
+pub mod parser;
+pub mod repl;
+mod runtime;
+mod source_map;
+pub mod timing;
+pub mod typing;
+
+// Version of the cranelift crates the compiler is built with, as in Cargo.toml
+const CRANELIFT_VERSION: &str = "0.107.2";
+
+// Versions of the compiler and its code generator and the default target, for bug reports
+pub fn version() -> String {
+    format!(
+        "aurora-compiler {}\ncranelift {}\ndefault target {}",
+        env!("CARGO_PKG_VERSION"),
+        CRANELIFT_VERSION,
+        CompileOptions::default().target
+    )
+}
+
+// A source file and its contents
+struct Source {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+// Prints every token of a source with the line and column it starts at
+pub fn print_tokens(source: impl Read) -> Result<()> {
+    let mut lexer = Lexer::new(source);
+    loop {
+        let Spanned { value, span } = lexer.next_spanned()?;
+        if value == Token::Eof {
+            return Ok(());
+        }
+        println!("{}:{} {}", span.start_line, span.start_col, value);
+    }
+}
+
+// Compiles a source file, along with the ones it imports, into the bytes of an object file.
+// Every error found is returned, each with the file, line and column it was found at
+pub fn compile(source: impl Read, options: CompileOptions) -> Result<Vec<u8>> {
+    compile_timed(source, options).map(|(object, _)| object)
+}
+
+// Same as `compile`, also returning the time spent in each pass. Lexing happens on demand
+// so it's counted as parsing
+pub fn compile_timed(
+    mut source: impl Read,
+    options: CompileOptions,
+) -> Result<(Vec<u8>, PassTimes)> {
+    let mut generator = Generator::new(&options)?;
+    generator.init_essential_lib()?;
+
+    let mut errors = vec![];
+    let mut sources = vec![];
+    generator.times.time("loading", || -> Result<()> {
+        // Read up front since the sources are parsed twice
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes)?;
+        let mut loaded = HashSet::from([canonical(Path::new(&options.source_name))]);
+        let source = Source {
+            name: options.source_name.clone(),
+            bytes,
+        };
+        load(source, &mut sources, &mut loaded, &mut errors);
+        Ok(())
+    })?;
+
+    // Definitions from every file share the module, so any of them can call the others
+    let start = Instant::now();
+    let types = declare_types(&sources);
+    let prototypes = declare_all(&sources, &types, &mut generator);
+    generator.times.add("parsing", start.elapsed());
+    for source in &sources {
+        if too_many_errors(&errors, &options) {
+            break;
+        }
+        compile_source(
+            source,
+            &types,
+            &prototypes,
+            &mut generator,
+            &options,
+            &mut errors,
+        )?;
+    }
+
+    if too_many_errors(&errors, &options) {
+        errors.truncate(options.max_errors);
+        errors.push(Error::TooManyErrors);
+    }
+    if !errors.is_empty() {
+        return Err(Error::from_vec(errors));
+    }
+    if let Some(entry) = &options.entry {
+        entry_point(entry, &prototypes, &mut generator)?;
+    }
+    if options.print_symbols {
+        generator.print_symbols();
+    }
+    let mut times = mem::take(&mut generator.times);
+    let object = times.time("emission", || generator.finish())?;
+    Ok((object, times))
+}
+
+fn compile_source(
+    source: &Source,
+    types: &[AuroraType],
+    prototypes: &[(Symbol, Prototype)],
+    generator: &mut Generator,
+    options: &CompileOptions,
+    errors: &mut Vec<Error>,
+) -> Result<()> {
+    let mut parser = Parser::new(Lexer::new(source.bytes.as_slice()));
+    for type_ in types {
+        parser.declare_type(type_)?;
+    }
+    for (name, prototype) in prototypes {
+        parser.declare_function(*name, prototype);
+    }
+    parser.print_result = options.print_result;
+    let renderer = options.renderer();
+
+    loop {
+        if too_many_errors(errors, options) {
+            return Ok(());
+        }
+        let token = match parser.lexer.peek(0) {
+            Ok(token) => token,
+            // The input that failed to lex is consumed, so this always makes progress
+            Err(error) => {
+                errors.push(locate(&source.name, &parser, error));
+                skip_to_item(&mut parser);
+                continue;
+            }
+        };
+        match token {
+            Token::Eof => break,
+            Token::SemiColon => {
+                parser.lexer.next_token()?;
+                continue;
+            }
+            // Declared up front, parsed again to report its errors
+            Token::Struct | Token::Enum => {
+                let declared = match token {
+                    Token::Struct => parser.struct_(),
+                    _ => parser.enum_(),
+                };
+                if let Err(error) = declared {
+                    errors.push(locate(&source.name, &parser, error));
+                    skip_to_item(&mut parser);
+                }
+            }
+            // Imported files were loaded up front
+            Token::Import => {
+                if let Err(error) = parser.import() {
+                    errors.push(locate(&source.name, &parser, error));
+                    skip_to_item(&mut parser);
+                }
+            }
+            Token::Def => {
+                match generator
+                    .times
+                    .time("parsing", || parser.definition())
+                    .and_then(|definition| generator.function(definition))
+                {
+                    Ok(_definition) => (),
+                    Err(error) => {
+                        for error in error.into_vec() {
+                            errors.push(locate(&source.name, &parser, error));
+                        }
+                        skip_to_item(&mut parser);
+                    }
+                }
+                report_warnings(
+                    &source.name,
+                    &mut parser,
+                    options,
+                    renderer.as_ref(),
+                    errors,
+                );
+            }
+            Token::Extern => {
+                let declared = parser.extern_().and_then(|prototypes| {
+                    prototypes
+                        .iter()
+                        .map(|prototype| generator.prototype(prototype, Linkage::Import))
+                        .collect::<Result<Vec<_>>>()
+                });
+                match declared {
+                    Ok(declared) if options.emit_ir => {
+                        for prototype in declared {
+                            println!("{}", prototype);
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(error) => {
+                        errors.push(locate(&source.name, &parser, error));
+                        skip_to_item(&mut parser);
+                    }
+                }
+            }
+            // Anything else is a statement of the implicit `main`
+            _ => {
+                let statement = generator
+                    .times
+                    .time("parsing", || parser.top_level_statement());
+                if let Err(error) = statement {
+                    for error in error.into_vec() {
+                        errors.push(locate(&source.name, &parser, error));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(main) = parser.implicit_main() {
+        let explicit_main = prototypes.iter().any(|(name, _)| *name == "main");
+        let compiled = main.and_then(|main| {
+            if explicit_main {
+                Err(Error::TopLevelWithMain)
+            } else {
+                generator.function(main)
+            }
+        });
+        if let Err(error) = compiled {
+            for error in error.into_vec() {
+                errors.push(locate(&source.name, &parser, error));
+            }
+        }
+        report_warnings(
+            &source.name,
+            &mut parser,
+            options,
+            renderer.as_ref(),
+            errors,
+        );
+    }
+    Ok(())
+}
+
+fn too_many_errors(errors: &[Error], options: &CompileOptions) -> bool {
+    options.max_errors != 0 && errors.len() >= options.max_errors
+}
+
+// Makes sure there's a `main` for the executable to start at. Another entry gets a `main`
+// calling it, which returns its exit code if it has one
+fn entry_point(
+    entry: &str,
+    prototypes: &[(Symbol, Prototype)],
+    generator: &mut Generator,
+) -> Result<()> {
+    let main_defined = generator.is_defined(Symbol::intern("main"));
+    if entry == "main" {
+        return if main_defined {
+            Ok(())
+        } else {
+            Err(Error::NoMainFunction(entry.to_string()))
+        };
+    }
+    if main_defined {
+        return Err(Error::EntryWithMain(entry.to_string()));
+    }
+    let prototype = prototypes.iter().find(|(name, prototype)| {
+        *name == entry
+            && prototype.parameters.is_empty()
+            && generator.is_defined(prototype.function_name)
+    });
+    let Some((_, prototype)) = prototype else {
+        return Err(Error::NoMainFunction(entry.to_string()));
+    };
+
+    let call = Expr {
+        expr_type: ExprType::Call(prototype.function_name, vec![]),
+        type_: prototype.return_type.clone(),
+        span: Span::default(),
+    };
+    let return_ = |value: Expr| Expr {
+        expr_type: ExprType::Return(Some(Box::new(value))),
+        type_: AuroraType::I32,
+        span: Span::default(),
+    };
+    let statements = match prototype.return_type {
+        AuroraType::I32 => vec![return_(call)],
+        AuroraType::Void => {
+            let zero = Expr {
+                expr_type: ExprType::Integer(0),
+                type_: AuroraType::I32,
+                span: Span::default(),
+            };
+            vec![call, return_(zero)]
+        }
+        _ => return Err(Error::InvalidEntry(entry.to_string())),
+    };
+    generator.function(Function {
+        prototype: Prototype {
+            function_name: Symbol::intern("main"),
+            parameters: vec![],
+            return_type: AuroraType::I32,
+            variadic: false,
+        },
+        body: Expr {
+            expr_type: ExprType::Block(statements),
+            type_: AuroraType::I32,
+            span: Span::default(),
+        },
+        line: 1,
+    })
+}
+
+// Prints the warnings found so far, or adds them to the errors with `--warnings-as-errors`
+fn report_warnings<R: Read>(
+    source_name: &str,
+    parser: &mut Parser<R>,
+    options: &CompileOptions,
+    renderer: &dyn Renderer,
+    errors: &mut Vec<Error>,
+) {
+    for (line, warning) in parser.warnings.drain(..) {
+        if !options.warn_unused && matches!(warning, Warning::UnusedVariable(_)) {
+            continue;
+        }
+        if options.warnings_as_errors {
+            // Warnings only know their line
+            let error = Box::new(Error::DeniedWarning(warning));
+            errors.push(Error::Located(source_name.to_string(), line, None, error));
+        } else {
+            renderer.render(&Diagnostic {
+                severity: Severity::Warning,
+                file: Some(source_name.to_string()),
+                line: Some(line),
+                col: None,
+                message: format!("{:?}", warning),
+            });
+        }
+    }
+}
+
+// Adds a source and, depth first, every file it imports. A file is only loaded once, which
+// also stops circular imports
+fn load(
+    source: Source,
+    sources: &mut Vec<Source>,
+    loaded: &mut HashSet<PathBuf>,
+    errors: &mut Vec<Error>,
+) {
+    let imports = imports(&source.bytes);
+    let name = source.name.clone();
+    let directory = Path::new(&name).parent().unwrap_or(Path::new(""));
+    sources.push(source);
+
+    for (line, pos, path) in imports {
+        // Relative to the importing file
+        let path = directory.join(path);
+        if !loaded.insert(canonical(&path)) {
+            continue;
+        }
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let source = Source {
+                    name: path.to_string_lossy().into_owned(),
+                    bytes,
+                };
+                load(source, sources, loaded, errors);
+            }
+            Err(error) => {
+                let error = Error::Import(path.display().to_string(), error);
+                errors.push(Error::Located(
+                    name.clone(),
+                    line,
+                    Some(pos),
+                    Box::new(error),
+                ));
+            }
+        }
+    }
+}
+
+// Paths of the `import` statements of a source, with the line and column of each. Lexing
+// errors are left for the parser to report
+fn imports(source: &[u8]) -> Vec<(usize, usize, String)> {
+    let mut lexer = Lexer::new(source);
+    let mut imports = vec![];
+    while let Ok(token) = lexer.next_spanned() {
+        match token.value {
+            Token::Eof => break,
+            Token::Import => {
+                if let Ok(Token::Str(path)) = lexer.next_token() {
+                    imports.push((token.span.start_line, token.span.start_col, path));
+                }
+            }
+            _ => (),
+        }
+    }
+    imports
+}
+
+// Identifies a file however it's reached, falling back to the path as given for sources
+// not on disk
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Errors without a position of their own are reported where the parser stopped
+fn locate<R: Read>(source_name: &str, parser: &Parser<R>, error: Error) -> Error {
+    let (line, pos, error) = match error {
+        Error::Positioned(line, pos, error) => (line, pos, *error),
+        error => (parser.lexer.get_line(), parser.lexer.get_pos(), error),
+    };
+    Error::Located(source_name.to_string(), line, Some(pos), Box::new(error))
+}
+
+// Resumes parsing at the next top level item after a failed one. Input that fails to lex
+// is skipped over as well, the lexer having already consumed it
+fn skip_to_item<R: Read>(parser: &mut Parser<R>) {
+    loop {
+        match parser.lexer.peek(0) {
+            Ok(
+                Token::Def
+                | Token::Extern
+                | Token::Import
+                | Token::Struct
+                | Token::Enum
+                | Token::Eof,
+            ) => return,
+            Ok(_) => {
+                parser.lexer.next_token().ok();
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+// Declares the structs and enums of every source ahead of the functions, so their
+// signatures can use any of them. A struct's fields may only use the types declared before
+// it. Errors are left for the second pass to report
+fn declare_types(sources: &[Source]) -> Vec<AuroraType> {
+    let mut types = vec![];
+    for source in sources {
+        let mut parser = Parser::new(Lexer::new(source.bytes.as_slice()));
+        for type_ in &types {
+            parser.declare_type(type_).ok();
+        }
+        loop {
+            let declared = match parser.lexer.peek(0) {
+                Ok(Token::Eof) => break,
+                // The lexer has already skipped what it failed on
+                Err(_) => continue,
+                Ok(Token::Struct) => parser.struct_(),
+                Ok(Token::Enum) => parser.enum_(),
+                Ok(_) => {
+                    parser.lexer.next_token().ok();
+                    continue;
+                }
+            };
+            match declared {
+                Ok(type_) => types.push(type_),
+                Err(_) => skip_to_item(&mut parser),
+            }
+        }
+    }
+    types
+}
+
+// First pass over the sources, declaring every function so that bodies can call the ones
+// defined further down. Returns each prototype with the name it's called by, its own
+// being the symbol. Errors are left for the second pass to report
+fn declare_all(
+    sources: &[Source],
+    types: &[AuroraType],
+    generator: &mut Generator,
+) -> Vec<(Symbol, Prototype)> {
+    let mut declarations = vec![];
+    for source in sources {
+        declarations.extend(declarations_of(&source.bytes, types));
+    }
+
+    // Overloaded definitions get a symbol naming their parameter types, externs always
+    // keep the one they're linked against
+    let mut overloads: HashMap<Symbol, Vec<Vec<AuroraType>>> = HashMap::new();
+    for (prototype, _) in &declarations {
+        let signatures = overloads.entry(prototype.function_name).or_default();
+        if !signatures.contains(&prototype.param_types()) {
+            signatures.push(prototype.param_types());
+        }
+    }
+
+    let mut prototypes = vec![];
+    for (mut prototype, linkage) in declarations {
+        let name = prototype.function_name;
+        if linkage == Linkage::Export && overloads[&name].len() > 1 {
+            prototype.function_name = prototype.mangled_name();
+        }
+        if generator.prototype(&prototype, linkage).is_ok() {
+            prototypes.push((name, prototype));
+        }
+    }
+    prototypes
+}
+
+fn declarations_of(source: &[u8], types: &[AuroraType]) -> Vec<(Prototype, Linkage)> {
+    let mut parser = Parser::new(Lexer::new(source));
+    for type_ in types {
+        parser.declare_type(type_).ok();
+    }
+    let mut declarations = vec![];
+    loop {
+        let declaration = match parser.lexer.peek(0) {
+            Ok(Token::Eof) => break,
+            Err(_) => continue,
+            Ok(Token::Def) => parser
+                .signature()
+                .map(|prototype| vec![(prototype, Linkage::Export)]),
+            Ok(Token::Extern) => parser.extern_().map(|prototypes| {
+                prototypes
+                    .into_iter()
+                    .map(|prototype| (prototype, Linkage::Import))
+                    .collect()
+            }),
+            Ok(_) => {
+                parser.lexer.next_token().ok();
+                continue;
+            }
+        };
+        match declaration {
+            Ok(declaration) => declarations.extend(declaration),
+            Err(_) => skip_to_item(&mut parser),
+        }
+    }
+    declarations
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    error::{Error, Result},
+    options::Options,
+    runtime,
+};
+
+pub struct Linker<'a> {
+    options: &'a Options,
+}
+
+impl<'a> Linker<'a> {
+    pub fn new(options: &'a Options) -> Self {
+        Self { options }
+    }
+
+    pub fn command(&self, object: &str, runtime_source: &Path) -> Command {
+        let mut command = Command::new(&self.options.linker);
+        if let Some(runtime) = &self.options.runtime {
+            command.arg(runtime);
+        }
+        command
+            .arg(runtime_source)
+            .arg(object)
+            .args(&self.options.link_args)
+            .arg("-o")
+            .arg(&self.options.output);
+        command
+    }
+
+    pub fn link(&self, object: &str) -> Result<()> {
+        let runtime_source = Path::new(object).with_file_name("aurora_runtime.c");
+        fs::write(&runtime_source, runtime::SOURCE).map_err(Error::io_at(&runtime_source))?;
+
+        let status = self
+            .command(object, &runtime_source)
+            .stdout(Stdio::null())
+            .status()
+            .map_err(Error::io_at(&self.options.linker))?;
+
+        if !status.success() {
+            return Err(Error::LinkFailed(self.options.linker.clone()));
+        }
+        Ok(())
+    }
+}
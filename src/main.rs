@@ -1,112 +1,64 @@
-use std::{fs::File, io::Write};
+use std::{fs::File, io::Write, path::Path, process};
 
-use cranelift_module::Linkage;
-use error::{Error, Result};
-use lexer::Lexer;
-use parser::Parser;
+use aurora_compiler::{
+    compile_timed,
+    error::{Error, Result},
+    link::Linker,
+    options::{CompileOptions, Emit, Options},
+    print_tokens, repl, version,
+};
 
-use crate::{gen::Generator, lexer::Token};
-
-mod ast;
-mod error;
-mod gen;
-mod lexer;
-mod parser;
-mod typing;
-
-fn main() -> Result<()> {
-    let filename = "example/test.au";
-    let exe_filename = "test.exe";
-
-    let file = File::open(filename)?;
-    let lexer = Lexer::new(file);
-    let mut parser = Parser::new(lexer);
-    let mut generator = Generator::new();
-
-    generator.init_essential_lib()?;
-
-    loop {
-        let token = match parser.lexer.peek(0) {
-            Ok(ref token) => *token,
-            Err(error) => {
-                eprintln!(
-                    "{}:{}:{} Error: {:?}",
-                    filename,
-                    parser.lexer.get_line(),
-                    parser.lexer.get_pos(),
-                    error
-                );
-                continue;
-            }
-        };
-        match token {
-            Token::Eof => break,
-            Token::SemiColon => {
-                parser.lexer.next_token()?;
-                continue;
-            }
-            Token::Def => {
-                match parser
-                    .definition()
-                    .and_then(|definition| generator.function(definition))
-                {
-                    Ok(_definition) => (),
-                    Err(error) => {
-                        parser.lexer.next_token()?;
-                        eprintln!(
-                            "{}:{}:{} Error: {:?}",
-                            filename,
-                            parser.lexer.get_line(),
-                            parser.lexer.get_pos(),
-                            error
-                        );
-                    }
-                }
-            }
-            Token::Extern => {
-                match parser
-                    .extern_()
-                    .and_then(|prototype| generator.prototype(&prototype, Linkage::Import))
-                {
-                    Ok(prototype) => println!("{}", prototype),
-                    Err(error) => {
-                        parser.lexer.next_token()?;
-                        eprintln!(
-                            "{}:{}:{} Error: {:?}",
-                            filename,
-                            parser.lexer.get_line(),
-                            parser.lexer.get_pos(),
-                            error
-                        );
-                    }
-                }
-            }
-            _ => return Err(Error::Unexpected("Unexpected top level token")),
+fn main() {
+    let options = match Options::from_env() {
+        Ok(options) => options,
+        Err(error) => {
+            error.report(CompileOptions::default().renderer().as_ref());
+            process::exit(1);
         }
+    };
+    if let Err(error) = run(&options) {
+        error.report(options.compile.renderer().as_ref());
+        process::exit(1);
     }
+}
 
-    let mut object = generator.module.finish().emit().unwrap();
-
-    let mut output_file = File::create("./build/test.o")?;
-    output_file.write_all(&mut object)?;
-    drop(output_file);
+fn run(options: &Options) -> Result<()> {
+    if options.version {
+        println!("{}", version());
+        return Ok(());
+    }
+    if options.repl {
+        return repl::run(options);
+    }
+    let filename = options.input.as_str();
 
-    _ = std::process::Command::new("gcc")
-        .args(&[
-            "./example/lib.c",
-            "./build/test.o",
-            "-lm",
-            "-o",
-            &format!("./build/{}", exe_filename),
-        ])
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status();
+    let file = File::open(filename).map_err(Error::io_at(filename))?;
+    if options.emit == Emit::Tokens {
+        return print_tokens(file);
+    }
+    let (object, mut times) = compile_timed(file, options.compile.clone())?;
 
+    // The tree and the IR are printed while compiling, leaving nothing to write
+    if matches!(options.emit, Emit::Obj | Emit::Exe) {
+        let object_filename = write_object(options, &object)?;
+        if options.emit == Emit::Exe {
+            times.time("linking", || Linker::new(options).link(&object_filename))?;
+        }
+    }
+    if options.time_passes {
+        times.print();
+    }
     Ok(())
 }
 
-#[no_mangle]
-pub extern "C" fn putfloatd(float: f64) {
-    println!("{}", float);
+// Writes the object next to the output, returning its path
+fn write_object(options: &Options, object: &[u8]) -> Result<String> {
+    let object_filename = Path::new(&options.output).with_extension("o");
+    let object_filename = object_filename.to_string_lossy().into_owned();
+
+    let mut output_file = File::create(&object_filename).map_err(Error::io_at(&object_filename))?;
+    output_file
+        .write_all(object)
+        .map_err(Error::io_at(&object_filename))?;
+    Ok(object_filename)
 }
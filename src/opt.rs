@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{BinaryOp, Expr, ExprType, UnaryOp},
+    intern::Symbol,
+    options::FloatCompare,
+    typing::AuroraType,
+};
+
+// Literal each variable in scope is bound to, `None` for variables that aren't constant
+type Constants = Vec<HashMap<Symbol, Option<ExprType>>>;
+
+// Replaces reads of immutable variables initialized with a literal by the literal, so
+// `fold_constants` can fold the expressions using them
+pub fn propagate_constants(expr: &mut Expr) {
+    propagate(expr, &mut vec![HashMap::new()]);
+}
+
+fn propagate(expr: &mut Expr, constants: &mut Constants) {
+    match &mut expr.expr_type {
+        ExprType::Variable(name) => {
+            let constant = constants.iter().rev().find_map(|scope| scope.get(name));
+            if let Some(literal) = constant.and_then(|constant| constant.as_ref().and_then(literal))
+            {
+                expr.expr_type = literal;
+            }
+        }
+        ExprType::Let(name, mutable, value) => {
+            if let Some(value) = value {
+                propagate(value, constants);
+            }
+            // Also recorded when not constant, shadowing outer constants of the same name
+            let constant = match value {
+                Some(value) if !*mutable => literal(&value.expr_type),
+                _ => None,
+            };
+            constants.last_mut().unwrap().insert(*name, constant);
+        }
+        ExprType::Block(exprs) => {
+            constants.push(HashMap::new());
+            exprs.iter_mut().for_each(|expr| propagate(expr, constants));
+            constants.pop();
+        }
+        ExprType::Binary(_, left, right) | ExprType::Intrinsic(_, left, right) => {
+            propagate(left, constants);
+            propagate(right, constants);
+        }
+        ExprType::Call(_, args) => args
+            .iter_mut()
+            .for_each(|arg| propagate(&mut arg.value, constants)),
+        ExprType::Assign(_, value) | ExprType::Return(Some(value)) => propagate(value, constants),
+        ExprType::IfElse(condition, then, else_) => {
+            propagate(condition, constants);
+            propagate(then, constants);
+            if let Some(else_) = else_ {
+                propagate(else_, constants);
+            }
+        }
+        ExprType::While(condition, body, _) => {
+            propagate(condition, constants);
+            propagate(body, constants);
+        }
+        ExprType::DoWhile(body, condition, _) => {
+            propagate(body, constants);
+            propagate(condition, constants);
+        }
+        ExprType::Match(value, arms, default) => {
+            propagate(value, constants);
+            arms.iter_mut()
+                .for_each(|(_, body)| propagate(body, constants));
+            propagate(default, constants);
+        }
+        ExprType::Index(array, index) => {
+            propagate(array, constants);
+            propagate(index, constants);
+        }
+        ExprType::AssignIndex(array, index, value) => {
+            propagate(array, constants);
+            propagate(index, constants);
+            propagate(value, constants);
+        }
+        // The operand is a place, only the index of an element can be replaced
+        ExprType::AddressOf(place) => {
+            if let ExprType::Index(_, index) = &mut place.expr_type {
+                propagate(index, constants);
+            }
+        }
+        ExprType::Deref(expr)
+        | ExprType::Field(expr, _)
+        | ExprType::Unary(_, expr)
+        | ExprType::Cast(expr)
+        | ExprType::Assert(expr, _)
+        | ExprType::Loop(expr, _) => propagate(expr, constants),
+        ExprType::Struct(values) => values
+            .iter_mut()
+            .for_each(|(_, value)| propagate(value, constants)),
+        ExprType::AssignDeref(pointer, value) => {
+            propagate(pointer, constants);
+            propagate(value, constants);
+        }
+        ExprType::Integer(_)
+        | ExprType::Float(_)
+        | ExprType::Boolean(_)
+        | ExprType::Str(_)
+        | ExprType::SizeOf(_)
+        | ExprType::Break(_)
+        | ExprType::Continue(_)
+        | ExprType::Return(None) => {}
+    }
+}
+
+// Strings aren't propagated, each use of a literal would get its own copy of the data
+fn literal(expr_type: &ExprType) -> Option<ExprType> {
+    match expr_type {
+        ExprType::Integer(value) => Some(ExprType::Integer(*value)),
+        ExprType::Float(value) => Some(ExprType::Float(*value)),
+        ExprType::Boolean(value) => Some(ExprType::Boolean(*value)),
+        _ => None,
+    }
+}
+
+// Replaces binary operations on literals with their result. Integer operations that
+// overflow are kept so codegen decides whether they wrap or trap
+pub fn fold_constants(expr: &mut Expr, float_compare: FloatCompare) {
+    match &mut expr.expr_type {
+        ExprType::Binary(_, left, right) | ExprType::Intrinsic(_, left, right) => {
+            fold_constants(left, float_compare);
+            fold_constants(right, float_compare);
+        }
+        ExprType::Call(_, args) => args
+            .iter_mut()
+            .for_each(|arg| fold_constants(&mut arg.value, float_compare)),
+        ExprType::Block(exprs) => exprs
+            .iter_mut()
+            .for_each(|expr| fold_constants(expr, float_compare)),
+        ExprType::Let(_, _, Some(value))
+        | ExprType::Assign(_, value)
+        | ExprType::Return(Some(value)) => fold_constants(value, float_compare),
+        ExprType::IfElse(condition, then, else_) => {
+            fold_constants(condition, float_compare);
+            fold_constants(then, float_compare);
+            if let Some(else_) = else_ {
+                fold_constants(else_, float_compare);
+            }
+        }
+        ExprType::While(condition, body, _) | ExprType::DoWhile(body, condition, _) => {
+            fold_constants(condition, float_compare);
+            fold_constants(body, float_compare);
+        }
+        ExprType::Match(value, arms, default) => {
+            fold_constants(value, float_compare);
+            arms.iter_mut()
+                .for_each(|(_, body)| fold_constants(body, float_compare));
+            fold_constants(default, float_compare);
+        }
+        ExprType::Index(array, index) => {
+            fold_constants(array, float_compare);
+            fold_constants(index, float_compare);
+        }
+        ExprType::AssignIndex(array, index, value) => {
+            fold_constants(array, float_compare);
+            fold_constants(index, float_compare);
+            fold_constants(value, float_compare);
+        }
+        ExprType::AddressOf(expr)
+        | ExprType::Deref(expr)
+        | ExprType::Field(expr, _)
+        | ExprType::Unary(_, expr)
+        | ExprType::Cast(expr)
+        | ExprType::Assert(expr, _)
+        | ExprType::Loop(expr, _) => fold_constants(expr, float_compare),
+        ExprType::Struct(values) => values
+            .iter_mut()
+            .for_each(|(_, value)| fold_constants(value, float_compare)),
+        ExprType::AssignDeref(pointer, value) => {
+            fold_constants(pointer, float_compare);
+            fold_constants(value, float_compare);
+        }
+        ExprType::Integer(_)
+        | ExprType::Float(_)
+        | ExprType::Boolean(_)
+        | ExprType::Str(_)
+        | ExprType::Variable(_)
+        | ExprType::SizeOf(_)
+        | ExprType::Let(_, _, None)
+        | ExprType::Break(_)
+        | ExprType::Continue(_)
+        | ExprType::Return(None) => {}
+    }
+
+    if let ExprType::Unary(op, operand) = &expr.expr_type {
+        let folded = match (op, &operand.expr_type) {
+            (UnaryOp::BitNot, ExprType::Integer(value)) => fit(!value, &operand.type_),
+            (UnaryOp::Negate, ExprType::Integer(value)) => value
+                .checked_neg()
+                .and_then(|value| fit(value, &operand.type_)),
+            (UnaryOp::Negate, ExprType::Float(value)) => {
+                expr.expr_type = ExprType::Float(-value);
+                return;
+            }
+            _ => None,
+        };
+        if let Some(value) = folded {
+            expr.expr_type = ExprType::Integer(value);
+        }
+        return;
+    }
+    if let ExprType::Binary(op, left, right) = &expr.expr_type {
+        if left.type_ != right.type_ {
+            return;
+        }
+        if let Some(folded) = fold_binary(
+            *op,
+            &left.expr_type,
+            &right.expr_type,
+            &left.type_,
+            float_compare,
+        ) {
+            expr.expr_type = folded;
+        }
+    }
+}
+
+fn fold_binary(
+    op: BinaryOp,
+    left: &ExprType,
+    right: &ExprType,
+    type_: &AuroraType,
+    float_compare: FloatCompare,
+) -> Option<ExprType> {
+    let folded = match (left, right) {
+        // A `u128` past the range of an `i128` is held by its bits, which these would
+        // compare and compute with as signed
+        (ExprType::Integer(left), ExprType::Integer(right))
+            if !type_.fits(*left) || !type_.fits(*right) =>
+        {
+            return None
+        }
+        (ExprType::Integer(left), ExprType::Integer(right)) => match op {
+            BinaryOp::Plus => ExprType::Integer(fit(left.checked_add(*right)?, type_)?),
+            BinaryOp::Minus => ExprType::Integer(fit(left.checked_sub(*right)?, type_)?),
+            BinaryOp::Times => ExprType::Integer(fit(left.checked_mul(*right)?, type_)?),
+            // Division by zero is left for codegen to report
+            BinaryOp::Divide => ExprType::Integer(fit(left.checked_div(*right)?, type_)?),
+            BinaryOp::Modulo => ExprType::Integer(fit(left.checked_rem(*right)?, type_)?),
+            BinaryOp::LessThan => ExprType::Boolean(left < right),
+            BinaryOp::LessEqual => ExprType::Boolean(left <= right),
+            BinaryOp::GreaterThan => ExprType::Boolean(left > right),
+            BinaryOp::GreaterEqual => ExprType::Boolean(left >= right),
+            BinaryOp::Equal => ExprType::Boolean(left == right),
+            BinaryOp::NotEqual => ExprType::Boolean(left != right),
+            BinaryOp::BitAnd => ExprType::Integer(fit(left & right, type_)?),
+            BinaryOp::BitOr => ExprType::Integer(fit(left | right, type_)?),
+            BinaryOp::BitXor => ExprType::Integer(fit(left ^ right, type_)?),
+            // Left for codegen, which masks the amount of a shift by the type's width
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => return None,
+            BinaryOp::And | BinaryOp::Or => return None,
+        },
+        (ExprType::Float(left), ExprType::Float(right)) => {
+            // Comparisons with NaN are true when unordered, Rust's being ordered ones.
+            // `!=` is true for NaN either way
+            let unordered =
+                float_compare == FloatCompare::Unordered && (left.is_nan() || right.is_nan());
+            match op {
+                BinaryOp::Plus => ExprType::Float(left + right),
+                BinaryOp::Minus => ExprType::Float(left - right),
+                BinaryOp::Times => ExprType::Float(left * right),
+                BinaryOp::Divide => ExprType::Float(left / right),
+                BinaryOp::Modulo => return None,
+                BinaryOp::LessThan => ExprType::Boolean(unordered || left < right),
+                BinaryOp::LessEqual => ExprType::Boolean(unordered || left <= right),
+                BinaryOp::GreaterThan => ExprType::Boolean(unordered || left > right),
+                BinaryOp::GreaterEqual => ExprType::Boolean(unordered || left >= right),
+                BinaryOp::Equal => ExprType::Boolean(unordered || left == right),
+                BinaryOp::NotEqual => ExprType::Boolean(left != right),
+                _ => return None,
+            }
+        }
+        (ExprType::Boolean(left), ExprType::Boolean(right)) => match op {
+            BinaryOp::And => ExprType::Boolean(*left && *right),
+            BinaryOp::Or => ExprType::Boolean(*left || *right),
+            BinaryOp::Equal => ExprType::Boolean(left == right),
+            BinaryOp::NotEqual => ExprType::Boolean(left != right),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(folded)
+}
+
+fn fit(value: i128, type_: &AuroraType) -> Option<i128> {
+    type_.fits(value).then_some(value)
+}
@@ -0,0 +1,331 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+use crate::error::{Error, Human, Json, Renderer, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    Wrap,
+    Trap,
+}
+
+// How float comparisons other than `!=` treat NaN, false for an ordered one and true for
+// an unordered one. `!=` is always true for NaN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatCompare {
+    Ordered,
+    Unordered,
+}
+
+// How errors and warnings are written to stderr
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+// Whether human readable diagnostics are colored, `Auto` only coloring them for a terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptLevel {
+    None,
+    Speed,
+    SpeedAndSize,
+}
+
+impl OptLevel {
+    // Value of cranelift's `opt_level` setting
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptLevel::None => "none",
+            OptLevel::Speed => "speed",
+            OptLevel::SpeedAndSize => "speed_and_size",
+        }
+    }
+}
+
+// Stage the driver stops after, along with what it leaves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    // Prints the tokens of the source
+    Tokens,
+    // Prints the tree of every function
+    Ast,
+    // Prints the IR of every function
+    Ir,
+    // Writes the object file
+    Obj,
+    // Links the object into an executable
+    Exe,
+}
+
+// Settings of the compilation itself, independent of where the source comes from and
+// what is done with the object
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    pub target: String,
+    pub opt_level: OptLevel,
+    // Prints the tree of every function to stdout
+    pub print_ast: bool,
+    // Prints the IR of every function to stdout
+    pub emit_ir: bool,
+    // Prints the assembly of every function to stdout
+    pub emit_asm: bool,
+    // Prints the signature of every function and the types of its bindings to stdout
+    pub explain_types: bool,
+    pub overflow: Overflow,
+    pub float_compare: FloatCompare,
+    // Checks integer divisors for zero, trapping with a code of its own rather than the
+    // processor's exception
+    pub checked_div: bool,
+    // Emits DWARF line tables, naming the source file as given here
+    pub debug_info: bool,
+    pub source_name: String,
+    // Lists the module's functions once compiled
+    pub print_symbols: bool,
+    // Reports variables that are never read to stderr
+    pub warn_unused: bool,
+    // Reports warnings as errors, failing the build
+    pub warnings_as_errors: bool,
+    pub error_format: ErrorFormat,
+    pub color: Color,
+    // Function an executable starts at, checked to be defined. Any other than `main` is
+    // called from a generated `main`
+    pub entry: Option<String>,
+    // Prints the value of a last top level statement that's an expression
+    pub print_result: bool,
+    // Errors reported before giving up on the rest of the sources, 0 for no limit
+    pub max_errors: usize,
+}
+
+impl CompileOptions {
+    // Writes diagnostics in the chosen `--error-format`
+    pub fn renderer(&self) -> Box<dyn Renderer> {
+        let color = match self.color {
+            Color::Auto => io::stderr().is_terminal(),
+            Color::Always => true,
+            Color::Never => false,
+        };
+        match self.error_format {
+            ErrorFormat::Json => Box::new(Json),
+            ErrorFormat::Human => Box::new(Human::new(color)),
+        }
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            opt_level: OptLevel::None,
+            print_ast: false,
+            emit_ir: false,
+            emit_asm: false,
+            explain_types: false,
+            overflow: Overflow::Wrap,
+            float_compare: FloatCompare::Ordered,
+            checked_div: false,
+            debug_info: false,
+            source_name: "<memory>".to_string(),
+            print_symbols: false,
+            warn_unused: true,
+            warnings_as_errors: false,
+            error_format: ErrorFormat::Human,
+            color: Color::Auto,
+            entry: None,
+            print_result: false,
+            max_errors: 20,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub input: String,
+    pub output: String,
+    pub linker: String,
+    pub link_args: Vec<String>,
+    // A C source linked in besides the embedded runtime
+    pub runtime: Option<String>,
+    pub emit: Emit,
+    // Prints the time spent in each pass to stderr
+    pub time_passes: bool,
+    // Reads lines from stdin to build and run one at a time instead of compiling a file
+    pub repl: bool,
+    // Prints the version instead of compiling anything
+    pub version: bool,
+    pub compile: CompileOptions,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            input: "example/test.au".to_string(),
+            output: "./build/test.exe".to_string(),
+            linker: "cc".to_string(),
+            link_args: vec!["-lm".to_string()],
+            runtime: None,
+            emit: Emit::Exe,
+            time_passes: false,
+            repl: false,
+            version: false,
+            compile: CompileOptions::default(),
+        }
+    }
+}
+
+impl Options {
+    pub fn from_env() -> Result<Self> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self> {
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            // `--flag=value` is the same as `--flag value`
+            let (arg, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = |flag: &'static str| {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(Error::MissingArgumentValue(flag))
+            };
+
+            match arg.as_str() {
+                "-o" => options.output = value("-o")?,
+                "--linker" => options.linker = value("--linker")?,
+                "--link-arg" => options.link_args.push(value("--link-arg")?),
+                "-L" => options.link_args.push(format!("-L{}", value("-L")?)),
+                "-l" => options.link_args.push(format!("-l{}", value("-l")?)),
+                "--runtime" => options.runtime = Some(value("--runtime")?),
+                "--no-runtime" => options.runtime = None,
+                "--emit-obj" => options.emit = Emit::Obj,
+                "--emit" => {
+                    options.emit = match value("--emit")?.as_str() {
+                        "tokens" => Emit::Tokens,
+                        "ast" => Emit::Ast,
+                        "ir" => Emit::Ir,
+                        "obj" => Emit::Obj,
+                        "exe" => Emit::Exe,
+                        other => {
+                            return Err(Error::InvalidArgumentValue("--emit", other.to_string()))
+                        }
+                    }
+                }
+                "--time-passes" => options.time_passes = true,
+                "--repl" => options.repl = true,
+                "--entry" => options.compile.entry = Some(value("--entry")?),
+                "--version" | "-V" => options.version = true,
+                "--target" => options.compile.target = value("--target")?,
+                "--opt-level" => {
+                    options.compile.opt_level = match value("--opt-level")?.as_str() {
+                        "none" => OptLevel::None,
+                        "speed" => OptLevel::Speed,
+                        "speed_and_size" => OptLevel::SpeedAndSize,
+                        other => {
+                            return Err(Error::InvalidArgumentValue(
+                                "--opt-level",
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--emit-ir" => options.compile.emit_ir = true,
+                "--emit-asm" => options.compile.emit_asm = true,
+                "--explain-types" => options.compile.explain_types = true,
+                "--g" => options.compile.debug_info = true,
+                "--checked-div" => options.compile.checked_div = true,
+                "--print-symbols" => options.compile.print_symbols = true,
+                "--warn-unused" => options.compile.warn_unused = true,
+                "--no-warn-unused" => options.compile.warn_unused = false,
+                "--warnings-as-errors" | "-Werror" => options.compile.warnings_as_errors = true,
+                "--overflow" => {
+                    options.compile.overflow = match value("--overflow")?.as_str() {
+                        "wrap" => Overflow::Wrap,
+                        "trap" => Overflow::Trap,
+                        other => {
+                            return Err(Error::InvalidArgumentValue(
+                                "--overflow",
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--float-compare" => {
+                    options.compile.float_compare = match value("--float-compare")?.as_str() {
+                        "ordered" => FloatCompare::Ordered,
+                        "unordered" => FloatCompare::Unordered,
+                        other => {
+                            return Err(Error::InvalidArgumentValue(
+                                "--float-compare",
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--error-format" => {
+                    options.compile.error_format = match value("--error-format")?.as_str() {
+                        "human" => ErrorFormat::Human,
+                        "json" => ErrorFormat::Json,
+                        other => {
+                            return Err(Error::InvalidArgumentValue(
+                                "--error-format",
+                                other.to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--color" => {
+                    options.compile.color = match value("--color")?.as_str() {
+                        "auto" => Color::Auto,
+                        "always" => Color::Always,
+                        "never" => Color::Never,
+                        other => {
+                            return Err(Error::InvalidArgumentValue("--color", other.to_string()))
+                        }
+                    }
+                }
+                "--max-errors" => {
+                    let max = value("--max-errors")?;
+                    options.compile.max_errors = max
+                        .parse()
+                        .map_err(|_| Error::InvalidArgumentValue("--max-errors", max))?;
+                }
+                // Attached forms, e.g. `-L./lib` or `-lfoo`
+                _ if arg.starts_with("-L") || arg.starts_with("-l") => options.link_args.push(arg),
+                _ if arg.starts_with('-') => return Err(Error::UnknownArgument(arg)),
+                _ => options.input = arg,
+            }
+        }
+        options.compile.source_name = options.input.clone();
+        match options.emit {
+            Emit::Ast => options.compile.print_ast = true,
+            Emit::Ir => options.compile.emit_ir = true,
+            _ => (),
+        }
+        // Only an executable has an entry, so one given for anything else would be ignored
+        if options.emit != Emit::Exe {
+            if let Some(entry) = options.compile.entry.take() {
+                return Err(Error::InvalidArgumentValue("--entry", entry));
+            }
+        } else if options.compile.entry.is_none() {
+            options.compile.entry = Some("main".to_string());
+        }
+
+        Ok(options)
+    }
+}
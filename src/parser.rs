@@ -1,187 +1,1134 @@
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Cursor, Read},
+    mem,
+};
 
 use crate::{
-    ast::{BinaryOp, Expr, ExprType, Function, Parameter, Prototype},
-    error::{Error, Result},
-    lexer::{Lexer, Token},
-    typing,
+    ast::{Argument, BinaryOp, Expr, ExprType, Function, Intrinsic, Parameter, Prototype, UnaryOp},
+    error::{Error, Result, Warning},
+    intern::Symbol,
+    lexer::{Lexer, Span, Token},
+    runtime,
+    typing::AuroraType,
 };
 
+// Statements outside of any function, which make up an implicit `main`
+#[derive(Default)]
+struct TopLevel {
+    statements: Vec<Expr>,
+    scope: HashMap<Symbol, AuroraType>,
+    unread: HashMap<Symbol, usize>,
+    // Line of the first statement
+    line: usize,
+    // Whether a statement failed, leaving `main` not worth generating
+    failed: bool,
+}
+
+// A top level item of a source, as returned by `Parser::parse_all`
+#[derive(Debug)]
+pub enum Item {
+    Function(Box<Function>),
+    Extern(Vec<Prototype>),
+    Import(String),
+    // A struct or an enum
+    Type(AuroraType),
+}
+
 pub struct Parser<R: Read> {
-    type_map: HashMap<String, &'static str>,
-    bin_precedence: HashMap<BinaryOp, i32>,
+    // Functions declared so far by name, several for an overloaded one. Their prototypes
+    // carry the symbol they're defined as
+    functions: HashMap<Symbol, Vec<Prototype>>,
+    // Struct and enum types declared so far by name
+    types: HashMap<String, AuroraType>,
+    // Variable types, one map per enclosing block with the innermost last
+    scopes: Vec<HashMap<Symbol, AuroraType>>,
+    // Line of every variable of the matching scope that hasn't been read yet
+    unread: Vec<HashMap<Symbol, usize>>,
+    top_level: TopLevel,
+    // Label of every loop around the statement being parsed, the innermost last
+    loops: Vec<Option<String>>,
+    // Whether the last statement parsed left out its semicolon, as the last one of a block
+    // may
+    unterminated: bool,
+    // Errors of statements skipped while parsing the current function
+    errors: Vec<Error>,
+    // Variables whose `let` failed before their type was known
+    poisoned: HashSet<Symbol>,
+    // Warnings and the line they're for, left for the caller to report
+    pub warnings: Vec<(usize, Warning)>,
+    // Prints the value of the last top level statement when it's an expression, for the REPL
+    pub print_result: bool,
     pub lexer: Lexer<R>,
 }
 
+impl Parser<Cursor<Vec<u8>>> {
+    // Parses a source held in memory
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &str) -> Self {
+        Self::new(Lexer::from_str(source))
+    }
+}
+
 impl<R: Read> Parser<R> {
     pub fn new(lexer: Lexer<R>) -> Self {
-        let mut bin_precedence = HashMap::new();
-        bin_precedence.insert(BinaryOp::LessThan, 10);
-        bin_precedence.insert(BinaryOp::Plus, 20);
-        bin_precedence.insert(BinaryOp::Minus, 20);
-        bin_precedence.insert(BinaryOp::Times, 40);
-        return Self {
-            type_map: HashMap::new(),
-            bin_precedence,
+        let mut functions = HashMap::new();
+        for prototype in runtime::prototypes() {
+            functions.insert(prototype.function_name, vec![prototype]);
+        }
+
+        Self {
+            functions,
+            types: HashMap::new(),
+            scopes: vec![],
+            unread: vec![],
+            top_level: TopLevel::default(),
+            loops: vec![],
+            unterminated: false,
+            errors: vec![],
+            poisoned: HashSet::new(),
+            warnings: vec![],
+            print_result: false,
             lexer,
-        };
+        }
     }
 
     pub fn definition(&mut self) -> Result<Function> {
+        let line = self.lexer.peek_spanned(0)?.span.start_line;
         self.eat(Token::Def)?;
         let prototype = self.prototype()?;
+        if prototype.variadic {
+            return Err(Error::Unexpected(
+                "variadic parameters outside of an extern",
+            ));
+        }
+
+        let parameters = prototype
+            .parameters
+            .iter()
+            .map(|par| (par.name, par.type_.clone()))
+            .collect();
+        self.push_scope(parameters);
+        let body = self.block(prototype.return_type.clone());
+        self.pop_scope();
 
-        for par in &prototype.parameters {
-            self.type_map.insert(par.name.clone(), par.type_);
+        let mut errors = mem::take(&mut self.errors);
+        match body {
+            Ok(body) if errors.is_empty() => Ok(Function {
+                prototype,
+                body,
+                line,
+            }),
+            Ok(_) => Err(Error::from_vec(errors)),
+            Err(error) => {
+                errors.push(error);
+                Err(Error::from_vec(errors))
+            }
         }
+    }
 
-        let body = self.block(prototype.return_type)?;
+    fn block(&mut self, type_: AuroraType) -> Result<Expr> {
+        let start = self.start()?;
+        self.eat(Token::OpenBracket)?;
+        self.push_scope(HashMap::new());
+        let exprs = self.statements(start);
+        let scope = self.pop_scope();
+        let mut exprs = exprs?;
+        self.resolve_lets(&mut exprs, &scope);
+        // A function body's last expression without a semicolon is the value it returns.
+        // Cleared so the statement holding a nested block isn't taken as unterminated
+        let unterminated = mem::take(&mut self.unterminated);
+        if type_ != AuroraType::Void && unterminated {
+            if let Some(last) = exprs.pop() {
+                exprs.push(Self::returned(last));
+            }
+        }
+        self.eat(Token::CloseBracket)?;
+        Ok(Expr {
+            expr_type: ExprType::Block(exprs),
+            type_,
+            span: self.span(start),
+        })
+    }
 
-        for par in &prototype.parameters {
-            self.type_map.remove(&par.name);
+    // Bindings declared without a type take the one of their first assignment
+    fn resolve_lets(&mut self, exprs: &mut [Expr], scope: &HashMap<Symbol, AuroraType>) {
+        for expr in exprs {
+            if let ExprType::Let(name, _, None) = &expr.expr_type {
+                if expr.type_ == AuroraType::Any {
+                    match &scope[name] {
+                        AuroraType::Any => {
+                            self.errors.push(Error::UnresolvedType(name.to_string()))
+                        }
+                        type_ => expr.type_ = type_.clone(),
+                    }
+                }
+            }
         }
+    }
 
-        Ok(Function { prototype, body })
+    // Parses the whole input, stopping at the first error. Functions can only call the ones
+    // defined before them. The implicit `main` made of the top level statements comes last.
+    // Malformed input is an error, never a panic, so this can be fed anything
+    pub fn parse_all(&mut self) -> Result<Vec<Item>> {
+        let mut items = vec![];
+        loop {
+            let item = match self.lexer.peek(0)? {
+                Token::Eof => break,
+                Token::SemiColon => {
+                    self.lexer.next_token()?;
+                    continue;
+                }
+                Token::Def => Item::Function(Box::new(self.definition()?)),
+                Token::Extern => Item::Extern(self.extern_()?),
+                Token::Import => Item::Import(self.import()?),
+                Token::Struct => Item::Type(self.struct_()?),
+                Token::Enum => Item::Type(self.enum_()?),
+                _ => {
+                    self.top_level_statement()?;
+                    continue;
+                }
+            };
+            items.push(item);
+        }
+        if let Some(main) = self.implicit_main() {
+            items.push(Item::Function(Box::new(main?)));
+        }
+        Ok(items)
+    }
+
+    // Parses a statement outside of any function for the implicit `main`. Its variables
+    // stay in scope for the following top level statements only
+    pub fn top_level_statement(&mut self) -> Result<()> {
+        if self.top_level.statements.is_empty() {
+            self.top_level.line = self.lexer.peek_spanned(0)?.span.start_line;
+        }
+        self.scopes.push(mem::take(&mut self.top_level.scope));
+        self.unread.push(mem::take(&mut self.top_level.unread));
+        let statement = self.terminated_statement();
+        self.top_level.unread = self.unread.pop().unwrap();
+        self.top_level.scope = self.scopes.pop().unwrap();
+
+        let mut errors = mem::take(&mut self.errors);
+        match statement {
+            Ok(statement) if errors.is_empty() => {
+                self.top_level.statements.push(statement);
+                Ok(())
+            }
+            Ok(_) => {
+                self.top_level.failed = true;
+                Err(Error::from_vec(errors))
+            }
+            Err(error) => {
+                if !matches!(error, Error::Poisoned) {
+                    errors.push(error);
+                }
+                self.top_level.failed = true;
+                self.recover(None)?;
+                // A stray `}` closes nothing at the top level
+                if *self.lexer.peek(0)? == Token::CloseBracket {
+                    self.lexer.next_token()?;
+                }
+                Err(Error::from_vec(errors))
+            }
+        }
     }
 
-    fn block(&mut self, type_: &'static str) -> Result<Expr> {
+    // The `main` made of the top level statements, returning 0, if there are any
+    pub fn implicit_main(&mut self) -> Option<Result<Function>> {
+        if self.top_level.statements.is_empty() {
+            return None;
+        }
+        let TopLevel {
+            mut statements,
+            scope,
+            unread,
+            line,
+            failed,
+        } = mem::take(&mut self.top_level);
+        self.scopes.push(scope);
+        self.unread.push(unread);
+        let scope = self.pop_scope();
+        self.resolve_lets(&mut statements, &scope);
+        let errors = mem::take(&mut self.errors);
+        // The statements that failed were reported already
+        if failed || !errors.is_empty() {
+            return Some(Err(Error::from_vec(errors)));
+        }
+
+        if self.print_result {
+            if let Some(last) = statements.pop() {
+                statements.push(Self::printed(last));
+            }
+        }
+        let zero = Expr {
+            expr_type: ExprType::Integer(0),
+            type_: AuroraType::I32,
+            span: Span::default(),
+        };
+        statements.push(Expr {
+            expr_type: ExprType::Return(Some(Box::new(zero))),
+            type_: AuroraType::I32,
+            span: Span::default(),
+        });
+        Some(Ok(Function {
+            prototype: Prototype {
+                function_name: Symbol::intern("main"),
+                parameters: vec![],
+                return_type: AuroraType::I32,
+                variadic: false,
+            },
+            body: Expr {
+                expr_type: ExprType::Block(statements),
+                type_: AuroraType::I32,
+                span: Span::default(),
+            },
+            line,
+        }))
+    }
+
+    // Returns the value of an expression, leaving statements without one as they are
+    fn returned(expr: Expr) -> Expr {
+        match expr.expr_type {
+            ExprType::Let(..)
+            | ExprType::Assign(..)
+            | ExprType::AssignIndex(..)
+            | ExprType::AssignDeref(..)
+            | ExprType::Return(_)
+            | ExprType::Break(_)
+            | ExprType::Continue(_) => expr,
+            _ => Expr {
+                type_: expr.type_.clone(),
+                span: expr.span,
+                expr_type: ExprType::Return(Some(Box::new(expr))),
+            },
+        }
+    }
+
+    // Passes an expression to the runtime function printing its type, if there's one
+    fn printed(expr: Expr) -> Expr {
+        let printer = match expr.expr_type {
+            ExprType::Let(..)
+            | ExprType::Assign(..)
+            | ExprType::AssignIndex(..)
+            | ExprType::AssignDeref(..) => None,
+            _ => Self::printer(&expr.type_),
+        };
+        match printer {
+            Some(printer) => Self::print_call(printer, expr),
+            None => expr,
+        }
+    }
+
+    // Runtime function printing a value of a type. Being C functions they take any narrower
+    // number
+    fn printer(type_: &AuroraType) -> Option<&'static str> {
+        if *type_ == AuroraType::Bool {
+            Some("putbool")
+        } else if type_.c_widens_to(&AuroraType::I64) {
+            Some("putint")
+        } else if type_.c_widens_to(&AuroraType::F64) {
+            Some("putfloatd")
+        } else {
+            None
+        }
+    }
+
+    fn print_call(printer: &str, expr: Expr) -> Expr {
+        let span = expr.span;
+        Expr {
+            expr_type: ExprType::Call(
+                Symbol::intern(printer),
+                vec![Argument {
+                    name: None,
+                    value: expr,
+                }],
+            ),
+            type_: AuroraType::Void,
+            span,
+        }
+    }
+
+    // The statements of a block up to its closing bracket, the opening one being at `open`
+    fn statements(&mut self, open: Span) -> Result<Vec<Expr>> {
         let mut exprs: Vec<Expr> = vec![];
-        self.eat(Token::OpenBracket)?;
+        let mut unreachable = false;
         loop {
-            let peek = (*self.lexer.peek(0)?).clone();
-            match peek {
-                Token::Return => {
-                    self.eat(Token::Return)?;
-                    let peek = self.lexer.peek(0)?;
-                    if *peek == Token::SemiColon {
-                        exprs.push(Expr {
-                            expr_type: ExprType::Return(None),
-                            type_: typing::VOID,
-                        })
-                    } else {
-                        let expr = Box::new(self.expr()?);
-                        exprs.push(Expr {
-                            type_: expr.type_,
-                            expr_type: ExprType::Return(Some(expr)),
-                        })
+            match self.lexer.peek(0)? {
+                Token::CloseBracket => break,
+                Token::Eof => return Err(Error::Unexpected("end of file").at(open)),
+                _ => (),
+            }
+            let line = self.lexer.peek_spanned(0)?.span.start_line;
+            let expr = self.terminated_statement();
+            match expr {
+                // Still parsed for its errors, but never generated
+                Ok(_) if unreachable => (),
+                Ok(_) if exprs.last().is_some_and(Expr::diverges) => {
+                    self.warnings.push((line, Warning::UnreachableCode));
+                    unreachable = true;
+                }
+                Ok(expr) => exprs.push(expr),
+                Err(error) => {
+                    self.push_error(error);
+                    self.recover(Some(open))?;
+                }
+            }
+        }
+        Ok(exprs)
+    }
+
+    // Keeps the error of a statement skipped, but for one the failed `let` of a variable it
+    // uses already makes up for
+    fn push_error(&mut self, error: Error) {
+        if !matches!(error, Error::Poisoned) {
+            self.errors.push(error);
+        }
+    }
+
+    // A statement and its semicolon, which statements ending in a block don't need
+    fn terminated_statement(&mut self) -> Result<Expr> {
+        self.unterminated = false;
+        match self.lexer.peek(0)? {
+            Token::If => self.if_else(),
+            Token::While => {
+                let start = self.start()?;
+                self.while_(None, start)
+            }
+            Token::Match => self.match_(),
+            Token::Loop => {
+                let start = self.start()?;
+                self.loop_(None, start)
+            }
+            // Eats its own semicolon
+            Token::Do => {
+                let start = self.start()?;
+                self.do_while(None, start)
+            }
+            Token::Label(_) => self.labeled_loop(),
+            Token::OpenBracket => self.block(AuroraType::Void),
+            _ => {
+                let expr = self.statement()?;
+                if *self.lexer.peek(0)? == Token::CloseBracket {
+                    self.unterminated = true;
+                } else {
+                    self.eat(Token::SemiColon)?;
+                }
+                Ok(expr)
+            }
+        }
+    }
+
+    fn push_scope(&mut self, scope: HashMap<Symbol, AuroraType>) {
+        self.scopes.push(scope);
+        self.unread.push(HashMap::new());
+    }
+
+    // Pops the innermost scope, warning about the variables in it that were never read
+    fn pop_scope(&mut self) -> HashMap<Symbol, AuroraType> {
+        let mut unread: Vec<_> = self.unread.pop().unwrap().into_iter().collect();
+        unread.sort_by_key(|&(_, line)| line);
+        for (name, line) in unread {
+            self.warnings
+                .push((line, Warning::UnusedVariable(name.to_string())));
+        }
+        self.scopes.pop().unwrap()
+    }
+
+    // Declares a variable in the innermost block, shadowing any outer one with the same name
+    fn declare(&mut self, name: Symbol, type_: AuroraType, line: usize) -> Result<()> {
+        self.poisoned.remove(&name);
+        let scope = self.scopes.last_mut().expect("variable outside of a block");
+        if scope.insert(name, type_).is_some() {
+            return Err(Error::VariableRedef);
+        }
+        // Names starting with an underscore are unused on purpose
+        if !name.as_str().starts_with('_') {
+            let unread = self.unread.last_mut().unwrap();
+            unread.insert(name, line);
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&AuroraType> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    // Marks the variable a name resolves to as read
+    fn read(&mut self, name: Symbol) {
+        let mut scopes = self.scopes.iter().zip(&mut self.unread).rev();
+        if let Some((_, unread)) = scopes.find(|(scope, _)| scope.contains_key(&name)) {
+            unread.remove(&name);
+        }
+    }
+
+    fn lookup_mut(&mut self, name: Symbol) -> Option<&mut AuroraType> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name))
+    }
+
+    // Skips the rest of a statement that failed to parse, so the next one can be checked.
+    // Running out of input is an error at the innermost bracket left open, `open` being
+    // the one of the enclosing block if there's one
+    fn recover(&mut self, open: Option<Span>) -> Result<()> {
+        // Brackets opened while skipping
+        let mut opened = vec![];
+        loop {
+            let token = self.lexer.peek_spanned(0)?;
+            match token.value {
+                Token::Eof => {
+                    return match opened.last().copied().or(open) {
+                        Some(open) => Err(Error::Unexpected("end of file").at(open)),
+                        None => Ok(()),
+                    };
+                }
+                Token::SemiColon if opened.is_empty() => {
+                    self.lexer.next_token()?;
+                    return Ok(());
+                }
+                Token::CloseBracket if opened.is_empty() => return Ok(()),
+                Token::CloseBracket => {
+                    opened.pop();
+                    self.lexer.next_token()?;
+                    if opened.is_empty() && *self.lexer.peek(0)? != Token::Else {
+                        return Ok(());
                     }
+                    continue;
                 }
-                Token::Let => exprs.push(self.let_()?),
-                Token::Identifier(_) if *self.lexer.peek(1)? == Token::Equal => {
-                    exprs.push(self.assign()?)
+                Token::OpenBracket => opened.push(token.span),
+                _ => (),
+            }
+            self.lexer.next_token()?;
+        }
+    }
+
+    fn statement(&mut self) -> Result<Expr> {
+        let start = self.start()?;
+        let peek = (*self.lexer.peek(0)?).clone();
+        match peek {
+            Token::Return => {
+                self.eat(Token::Return)?;
+                let peek = self.lexer.peek(0)?;
+                if *peek == Token::SemiColon {
+                    Ok(Expr {
+                        expr_type: ExprType::Return(None),
+                        type_: AuroraType::Void,
+                        span: self.span(start),
+                    })
+                } else {
+                    let expr = Box::new(self.expr()?);
+                    Ok(Expr {
+                        type_: expr.type_.clone(),
+                        expr_type: ExprType::Return(Some(expr)),
+                        span: self.span(start),
+                    })
                 }
-                _ => exprs.push(self.expr()?),
             }
-            self.eat(Token::SemiColon)?;
-            let peek = self.lexer.peek(0)?;
-            if *peek == Token::CloseBracket {
-                self.eat(Token::CloseBracket)?;
-                break;
+            Token::Let => self.let_(),
+            Token::Break | Token::Continue => {
+                let keyword = self.lexer.next_token()?;
+                let label = if let Token::Label(_) = self.lexer.peek(0)? {
+                    let Token::Label(label) = self.lexer.next_token()? else {
+                        unreachable!()
+                    };
+                    if !self.loops.contains(&Some(label.clone())) {
+                        return Err(Error::UnknownLabel(label));
+                    }
+                    Some(label)
+                } else {
+                    None
+                };
+                let (keyword, expr_type) = match keyword {
+                    Token::Break => ("break", ExprType::Break(label)),
+                    _ => ("continue", ExprType::Continue(label)),
+                };
+                if self.loops.is_empty() {
+                    return Err(Error::OutsideLoop(keyword));
+                }
+                Ok(Expr {
+                    expr_type,
+                    type_: AuroraType::Void,
+                    span: self.span(start),
+                })
+            }
+            Token::Identifier(_) if *self.lexer.peek(1)? == Token::Equal => self.assign(),
+            _ => {
+                let Expr {
+                    expr_type,
+                    type_,
+                    span,
+                } = self.expr()?;
+                match expr_type {
+                    ExprType::Index(array, index) if *self.lexer.peek(0)? == Token::Equal => {
+                        self.assign_index(array, index, type_, start)
+                    }
+                    ExprType::Deref(pointer) if *self.lexer.peek(0)? == Token::Equal => {
+                        self.assign_deref(pointer, type_, start)
+                    }
+                    expr_type => Ok(Expr {
+                        expr_type,
+                        type_,
+                        span,
+                    }),
+                }
             }
         }
+    }
+
+    fn if_else(&mut self) -> Result<Expr> {
+        let start = self.start()?;
+        self.eat(Token::If)?;
+        let condition = self.expr()?;
+        let then = self.block(AuroraType::Void)?;
+        let else_ = if *self.lexer.peek(0)? == Token::Else {
+            self.eat(Token::Else)?;
+            // `else if` chains nest as the else branch
+            let else_ = match self.lexer.peek(0)? {
+                Token::If => self.if_else()?,
+                _ => self.block(AuroraType::Void)?,
+            };
+            Some(Box::new(else_))
+        } else {
+            None
+        };
         Ok(Expr {
-            expr_type: ExprType::Block(exprs),
-            type_,
+            expr_type: ExprType::IfElse(Box::new(condition), Box::new(then), else_),
+            type_: AuroraType::Void,
+            span: self.span(start),
+        })
+    }
+
+    // `'name: while ...` or `'name: loop ...`
+    fn labeled_loop(&mut self) -> Result<Expr> {
+        let start = self.start()?;
+        let Token::Label(label) = self.lexer.next_token()? else {
+            unreachable!()
+        };
+        self.eat(Token::Colon)?;
+        match self.lexer.peek(0)? {
+            Token::While => self.while_(Some(label), start),
+            Token::Do => self.do_while(Some(label), start),
+            Token::Loop => self.loop_(Some(label), start),
+            _ => Err(Error::Unexpected("label not followed by a loop")),
+        }
+    }
+
+    // `start` is where the label is, if there's one
+    fn while_(&mut self, label: Option<String>, start: Span) -> Result<Expr> {
+        self.eat(Token::While)?;
+        let condition = self.expr()?;
+        let body = self.loop_body(label.clone())?;
+        Ok(Expr {
+            expr_type: ExprType::While(Box::new(condition), Box::new(body), label),
+            type_: AuroraType::Void,
+            span: self.span(start),
+        })
+    }
+
+    // `do { ... } while condition;`, whose body runs once before the condition is checked
+    fn do_while(&mut self, label: Option<String>, start: Span) -> Result<Expr> {
+        self.eat(Token::Do)?;
+        let body = self.loop_body(label.clone())?;
+        self.eat(Token::While)?;
+        let condition = self.expr()?;
+        self.eat(Token::SemiColon)?;
+        Ok(Expr {
+            expr_type: ExprType::DoWhile(Box::new(body), Box::new(condition), label),
+            type_: AuroraType::Void,
+            span: self.span(start),
+        })
+    }
+
+    // `loop { ... }`, only left by a `break` or a `return`
+    fn loop_(&mut self, label: Option<String>, start: Span) -> Result<Expr> {
+        self.eat(Token::Loop)?;
+        let body = self.loop_body(label.clone())?;
+        Ok(Expr {
+            expr_type: ExprType::Loop(Box::new(body), label),
+            type_: AuroraType::Void,
+            span: self.span(start),
+        })
+    }
+
+    // The block of a loop, in which `break` and `continue` are allowed
+    fn loop_body(&mut self, label: Option<String>) -> Result<Expr> {
+        self.loops.push(label);
+        let body = self.block(AuroraType::Void);
+        self.loops.pop();
+        body
+    }
+
+    // `match value { pattern => { ... }, _ => { ... } }` on an integer or an enum, each
+    // pattern a literal of the value's type and the `_` arm coming last
+    fn match_(&mut self) -> Result<Expr> {
+        let start = self.start()?;
+        self.eat(Token::Match)?;
+        let value = self.expr()?;
+        if !value.type_.is_integer() && !matches!(value.type_, AuroraType::Enum(..)) {
+            return Err(Error::NotMatchable(value.type_));
+        }
+        self.eat(Token::OpenBracket)?;
+        let mut arms: Vec<(i128, Expr)> = vec![];
+        let default = loop {
+            match self.lexer.peek(0)? {
+                Token::Identifier(name) if name.as_str() == "_" => {
+                    self.lexer.next_token()?;
+                    self.eat(Token::FatArrow)?;
+                    break self.block(AuroraType::Void)?;
+                }
+                Token::CloseBracket => return Err(Error::MissingDefaultArm),
+                _ => (),
+            }
+            let pattern = Self::literal_as(self.primary()?, &value.type_)?;
+            let ExprType::Integer(matched) = pattern.expr_type else {
+                return Err(Error::Unexpected(
+                    "pattern, expecting an integer literal or an enum variant",
+                ));
+            };
+            if pattern.type_ != value.type_ {
+                return Err(Error::MismatchedTypes(value.type_, pattern.type_));
+            }
+            if arms.iter().any(|(pattern, _)| *pattern == matched) {
+                return Err(Error::UnreachableArm);
+            }
+            self.eat(Token::FatArrow)?;
+            arms.push((matched, self.block(AuroraType::Void)?));
+            if *self.lexer.peek(0)? == Token::Comma {
+                self.eat(Token::Comma)?;
+            }
+        };
+        if *self.lexer.peek(0)? == Token::Comma {
+            self.eat(Token::Comma)?;
+        }
+        self.eat(Token::CloseBracket)?;
+        Ok(Expr {
+            expr_type: ExprType::Match(Box::new(value), arms, Box::new(default)),
+            type_: AuroraType::Void,
+            span: self.span(start),
         })
     }
 
     fn let_(&mut self) -> Result<Expr> {
+        let start = self.start()?;
+        let line = start.start_line;
         self.eat(Token::Let)?;
+        let mutable = *self.lexer.peek(0)? == Token::Mut;
+        if mutable {
+            self.eat(Token::Mut)?;
+        }
         let name = self.identifier()?;
-        let token = self.lexer.peek(0)?;
-        let mut type_: &str = typing::ANY;
-        if *token == Token::Colon {
-            self.eat(Token::Colon)?;
-            type_ = typing::get_const_str_from_string(self.identifier()?);
+        // Calls still reach the function, but reading the two apart gets confusing
+        if self.functions.contains_key(&name) {
+            self.warnings
+                .push((line, Warning::ShadowedFunction(name.to_string())));
+        }
+        let mut type_ = AuroraType::Any;
+        match self.binding(&mut type_) {
+            Ok(value) => {
+                let type_ = value.as_ref().map_or(type_, |value| value.type_.clone());
+                self.declare(name, type_.clone(), line)?;
+                Ok(Expr {
+                    expr_type: ExprType::Let(name, mutable, value.map(Box::new)),
+                    type_,
+                    span: self.span(start),
+                })
+            }
+            // Still declared, with its annotated type if it has one, so uses of it don't
+            // error too
+            Err(error) => {
+                let _ = self.declare(name, type_.clone(), line);
+                self.read(name);
+                if type_ == AuroraType::Any {
+                    self.poisoned.insert(name);
+                }
+                Err(error)
+            }
         }
+    }
 
-        let peek = self.lexer.peek(0)?;
-        match peek {
+    // The annotated type and value of a `let`, past its name. `type_` is left as `Any`
+    // without an annotation
+    fn binding(&mut self, type_: &mut AuroraType) -> Result<Option<Expr>> {
+        if *self.lexer.peek(0)? == Token::Colon {
+            self.eat(Token::Colon)?;
+            *type_ = self.type_()?;
+        }
+        match self.lexer.peek(0)? {
             Token::Equal => {
                 self.eat(Token::Equal)?;
                 let expr = self.expr()?;
-                if type_.is_empty() || expr.type_ == type_ {
-                    if let Some(_) = self.type_map.insert(name.clone(), expr.type_) {
-                        return Err(Error::VariableRedef);
-                    }
-                    Ok(Expr {
-                        type_: expr.type_,
-                        expr_type: ExprType::Let(name, Some(Box::new(expr))),
-                    })
-                } else {
-                    dbg!(type_, &expr);
-                    Err(Error::MismatchedTypes(type_, expr.type_))
+                let expr = Self::literal_as(expr, type_)?;
+                if *type_ != AuroraType::Any && expr.type_ != *type_ {
+                    return Err(Error::MismatchedTypes(type_.clone(), expr.type_).at(expr.span));
                 }
+                Ok(Some(expr))
             }
-            Token::SemiColon => {
-                if let Some(_) = self.type_map.insert(name.clone(), type_) {
-                    return Err(Error::VariableRedef);
-                }
-                if type_ == typing::ANY {
-                    return Err(Error::Undefined("type".to_string())); //TODO: Remove this and try to infer it later via type_map
+            Token::SemiColon => Ok(None),
+            _ => Err(Error::Unexpected("Expected ';' or '='").at(self.start()?)),
+        }
+    }
+
+    // An integer literal takes the integer type of the variable it's given to, as long as
+    // its value fits in it. A float literal is narrowed to an `f32`
+    fn literal_as(expr: Expr, type_: &AuroraType) -> Result<Expr> {
+        match expr.expr_type {
+            ExprType::Integer(value)
+                if expr.type_.is_integer() && type_.is_integer() && expr.type_ != *type_ =>
+            {
+                if !type_.fits_literal(value, &expr.type_) {
+                    let literal = expr.type_.literal(value);
+                    return Err(
+                        Error::IntegerLiteralOutOfRange(literal, type_.clone()).at(expr.span)
+                    );
                 }
                 Ok(Expr {
-                    expr_type: ExprType::Let(name, None),
-                    type_,
+                    expr_type: ExprType::Integer(value),
+                    type_: type_.clone(),
+                    span: expr.span,
                 })
             }
-            _ => Err(Error::Unexpected("Expected ';' or '='")),
+            ExprType::Float(value) if *type_ == AuroraType::F32 => Ok(Expr {
+                expr_type: ExprType::Float(value),
+                type_: AuroraType::F32,
+                span: expr.span,
+            }),
+            _ => Ok(expr),
         }
     }
 
+    // Literal arguments take the type of the parameter they're given to
+    fn literal_args(args: Vec<Argument>, prototype: &Prototype) -> Result<Vec<Argument>> {
+        let mut literals = vec![];
+        for (i, arg) in args.into_iter().enumerate() {
+            let value = match prototype.parameter(i, &arg) {
+                Some(parameter) => Self::literal_as(arg.value, &parameter.type_)?,
+                None => arg.value,
+            };
+            literals.push(Argument {
+                name: arg.name,
+                value,
+            });
+        }
+        Ok(literals)
+    }
+
     fn assign(&mut self) -> Result<Expr> {
+        let start = self.start()?;
         let name = self.identifier()?;
         self.eat(Token::Equal)?;
         let expr = self.expr()?;
+        let expr = match self.lookup(name) {
+            Some(type_) => Self::literal_as(expr, type_)?,
+            None => expr,
+        };
+        match self.lookup_mut(name) {
+            Some(type_) if *type_ == AuroraType::Any => *type_ = expr.type_.clone(),
+            Some(type_) if *type_ != expr.type_ => {
+                return Err(Error::MismatchedTypes(type_.clone(), expr.type_))
+            }
+            Some(_) => (),
+            None => return Err(Error::Undefined(format!("identifier {}", name)).at(start)),
+        }
         Ok(Expr {
-            type_: expr.type_,
+            type_: expr.type_.clone(),
             expr_type: ExprType::Assign(name, Box::new(expr)),
+            span: self.span(start),
         })
     }
 
+    fn assign_index(
+        &mut self,
+        array: Box<Expr>,
+        index: Box<Expr>,
+        element: AuroraType,
+        start: Span,
+    ) -> Result<Expr> {
+        self.eat(Token::Equal)?;
+        let value = Self::literal_as(self.expr()?, &element)?;
+        if value.type_ != element {
+            return Err(Error::MismatchedTypes(element, value.type_));
+        }
+        Ok(Expr {
+            type_: value.type_.clone(),
+            expr_type: ExprType::AssignIndex(array, index, Box::new(value)),
+            span: self.span(start),
+        })
+    }
+
+    fn assign_deref(
+        &mut self,
+        pointer: Box<Expr>,
+        pointee: AuroraType,
+        start: Span,
+    ) -> Result<Expr> {
+        self.eat(Token::Equal)?;
+        if pointee.is_aggregate() {
+            return Err(Error::Unexpected("assignment of a whole array or struct"));
+        }
+        let value = Self::literal_as(self.expr()?, &pointee)?;
+        if value.type_ != pointee {
+            return Err(Error::MismatchedTypes(pointee, value.type_));
+        }
+        Ok(Expr {
+            type_: value.type_.clone(),
+            expr_type: ExprType::AssignDeref(pointer, Box::new(value)),
+            span: self.span(start),
+        })
+    }
+
+    // Where the next token starts, for the span of what's parsed from there
+    fn start(&mut self) -> Result<Span> {
+        Ok(self.lexer.peek_spanned(0)?.span)
+    }
+
+    // From `start` to the end of the last token parsed
+    fn span(&self, start: Span) -> Span {
+        self.lexer.span_from(start)
+    }
+
     fn eat(&mut self, token: Token) -> Result<()> {
         let current_token = self.lexer.next_token()?;
         if current_token != token {
-            return Err(Error::UnexpectedToken(token, current_token));
+            return Err(Error::UnexpectedToken(
+                Box::new(token),
+                Box::new(current_token),
+            ));
         }
         Ok(())
     }
 
     fn prototype(&mut self) -> Result<Prototype> {
         let function_name = self.identifier()?;
-        let parameters = self.parameters()?;
+        let (parameters, variadic) = self.parameters()?;
         let return_type = match self.lexer.peek(0)? {
-            Token::Identifier(_) => typing::get_const_str_from_string(self.identifier()?),
-            _ => typing::VOID,
+            Token::Identifier(_) | Token::Star | Token::OpenSquare => self.type_()?,
+            _ => AuroraType::Void,
         };
 
-        if let Some(_) = self.type_map.insert(function_name.clone(), return_type) {
-            return Err(Error::FunctionRedef);
-        }
-
-        Ok(Prototype {
+        let mut prototype = Prototype {
             function_name,
             parameters,
             return_type,
-        })
+            variadic,
+        };
+
+        // Functions are declared ahead of their definition, so only a conflicting
+        // signature is a redefinition here
+        let param_types = prototype.param_types();
+        let overloads = self.functions.entry(function_name).or_default();
+        match overloads
+            .iter()
+            .find(|declared| declared.param_types() == param_types)
+        {
+            Some(declared) if declared.return_type != prototype.return_type => {
+                return Err(Error::FunctionRedef)
+            }
+            Some(declared) => prototype.function_name = declared.function_name,
+            None => overloads.push(prototype.clone()),
+        }
+        Ok(prototype)
     }
 
-    pub fn extern_(&mut self) -> Result<Prototype> {
+    // `extern fn name(...) type` or a braced block of them, `extern { fn name(...) type; ... }`.
+    // The `fn` may be left out
+    pub fn extern_(&mut self) -> Result<Vec<Prototype>> {
         self.eat(Token::Extern)?;
+        if *self.lexer.peek(0)? != Token::OpenBracket {
+            return Ok(vec![self.extern_prototype()?]);
+        }
+        self.eat(Token::OpenBracket)?;
+        let mut prototypes = vec![];
+        while *self.lexer.peek(0)? != Token::CloseBracket {
+            prototypes.push(self.extern_prototype()?);
+            self.eat(Token::SemiColon)?;
+        }
+        self.eat(Token::CloseBracket)?;
+        Ok(prototypes)
+    }
+
+    fn extern_prototype(&mut self) -> Result<Prototype> {
+        if *self.lexer.peek(0)? == Token::Def {
+            self.eat(Token::Def)?;
+        }
         self.prototype()
     }
 
-    fn identifier(&mut self) -> Result<String> {
+    // `import "path";`, returning the path
+    pub fn import(&mut self) -> Result<String> {
+        self.eat(Token::Import)?;
+        let path = match self.lexer.next_token()? {
+            Token::Str(path) => path,
+            _ => return Err(Error::Unexpected("token, expecting the path to import")),
+        };
+        self.eat(Token::SemiColon)?;
+        Ok(path)
+    }
+
+    // `struct Name { field: type, ... }`, declaring the struct
+    pub fn struct_(&mut self) -> Result<AuroraType> {
+        self.eat(Token::Struct)?;
+        let name = self.identifier()?.to_string();
+        self.eat(Token::OpenBracket)?;
+        let mut fields: Vec<(String, AuroraType)> = vec![];
+        while *self.lexer.peek(0)? != Token::CloseBracket {
+            let field = self.identifier()?.to_string();
+            self.eat(Token::Colon)?;
+            let type_ = self.type_()?;
+            if type_ == AuroraType::Void {
+                return Err(Error::Unexpected("type of a field"));
+            }
+            if fields.iter().any(|(name, _)| *name == field) {
+                return Err(Error::DuplicateField(field));
+            }
+            fields.push((field, type_));
+            if *self.lexer.peek(0)? != Token::CloseBracket {
+                self.eat(Token::Comma)?;
+            }
+        }
+        self.eat(Token::CloseBracket)?;
+
+        let struct_ = AuroraType::Struct(name, fields);
+        self.declare_type(&struct_)?;
+        Ok(struct_)
+    }
+
+    // `enum Name { Variant, ... }`, declaring the enum
+    pub fn enum_(&mut self) -> Result<AuroraType> {
+        self.eat(Token::Enum)?;
+        let name = self.identifier()?.to_string();
+        self.eat(Token::OpenBracket)?;
+        let mut variants: Vec<String> = vec![];
+        while *self.lexer.peek(0)? != Token::CloseBracket {
+            let variant = self.identifier()?.to_string();
+            if variants.contains(&variant) {
+                return Err(Error::DuplicateVariant(variant));
+            }
+            variants.push(variant);
+            if *self.lexer.peek(0)? != Token::CloseBracket {
+                self.eat(Token::Comma)?;
+            }
+        }
+        self.eat(Token::CloseBracket)?;
+
+        let enum_ = AuroraType::Enum(name, variants);
+        self.declare_type(&enum_)?;
+        Ok(enum_)
+    }
+
+    // Declares a struct or an enum ahead of its definition. Declaring the same one again
+    // is allowed
+    pub fn declare_type(&mut self, type_: &AuroraType) -> Result<()> {
+        let (AuroraType::Struct(name, _) | AuroraType::Enum(name, _)) = type_ else {
+            return Err(Error::Unexpected("declaration of a builtin type"));
+        };
+        match self.types.get(name) {
+            Some(declared) if declared != type_ => Err(Error::TypeRedef(name.clone())),
+            _ => {
+                self.types.insert(name.clone(), type_.clone());
+                Ok(())
+            }
+        }
+    }
+
+    // Parses a definition's prototype, skipping over its body
+    pub fn signature(&mut self) -> Result<Prototype> {
+        self.eat(Token::Def)?;
+        let prototype = self.prototype()?;
+        self.eat(Token::OpenBracket)?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.lexer.next_token()? {
+                Token::OpenBracket => depth += 1,
+                Token::CloseBracket => depth -= 1,
+                Token::Eof => return Err(Error::Unexpected("end of file")),
+                _ => (),
+            }
+        }
+        Ok(prototype)
+    }
+
+    // Declares a function ahead of its definition, under the name it's called by
+    pub fn declare_function(&mut self, name: Symbol, prototype: &Prototype) {
+        let overloads = self.functions.entry(name).or_default();
+        overloads.push(prototype.clone());
+    }
+
+    // Picks the function a call is to, by the types of its arguments when overloaded
+    fn resolve(&self, name: Symbol, args: &[Argument], span: Span) -> Result<&Prototype> {
+        let Some(overloads) = self.functions.get(&name) else {
+            return Err(Error::Undefined(format!("function {}", name)).at(span));
+        };
+        // Calls to a function that isn't overloaded are checked in detail while generating
+        if let [prototype] = overloads.as_slice() {
+            return Ok(prototype);
+        }
+        // An overload taking the arguments as they are wins over the ones their literals
+        // would have to narrow to, of which there must only be one
+        let types = || args.iter().map(|arg| arg.value.type_.clone()).collect();
+        if let Some(prototype) = overloads
+            .iter()
+            .find(|prototype| prototype.accepts(args, false))
+        {
+            return Ok(prototype);
+        }
+        let mut narrowed = overloads
+            .iter()
+            .filter(|prototype| prototype.accepts(args, true));
+        match (narrowed.next(), narrowed.next()) {
+            (Some(prototype), None) => Ok(prototype),
+            (Some(_), Some(_)) => Err(Error::AmbiguousOverload(name.to_string(), types()).at(span)),
+            (None, _) => Err(Error::NoMatchingOverload(name.to_string(), types()).at(span)),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<Symbol> {
         match self.lexer.next_token()? {
             Token::Identifier(identifier) => Ok(identifier),
             _ => Err(Error::Unexpected("token, expecting identifier")),
         }
     }
 
-    fn parameters(&mut self) -> Result<Vec<Parameter>> {
+    fn type_(&mut self) -> Result<AuroraType> {
+        match self.lexer.peek(0)? {
+            Token::OpenSquare => return self.array_type(),
+            Token::Star => {
+                self.eat(Token::Star)?;
+                return Ok(AuroraType::Ptr(Box::new(self.type_()?)));
+            }
+            _ => (),
+        }
+        let name = self.identifier()?.to_string();
+        AuroraType::from_string(&name)
+            .or_else(|| self.types.get(&name).cloned())
+            .ok_or(Error::Undefined(format!("type {}", name)))
+    }
+
+    // `[T; N]`
+    fn array_type(&mut self) -> Result<AuroraType> {
+        self.eat(Token::OpenSquare)?;
+        let element = self.type_()?;
+        if matches!(
+            element,
+            AuroraType::Array(..) | AuroraType::Struct(..) | AuroraType::Void
+        ) {
+            return Err(Error::Unexpected("element type of an array"));
+        }
+        self.eat(Token::SemiColon)?;
+        let length = match self.lexer.next_token()? {
+            Token::Integer(length, None) if length > 0 => length as usize,
+            _ => return Err(Error::Unexpected("length of an array")),
+        };
+        self.eat(Token::CloseSquare)?;
+        Ok(AuroraType::Array(Box::new(element), length))
+    }
+
+    fn parameters(&mut self) -> Result<(Vec<Parameter>, bool)> {
         self.eat(Token::OpenParen)?;
         let mut params = vec![];
         let mut accept_more = true;
+        let mut variadic = false;
         loop {
             match self.lexer.peek(0)? {
                 Token::Identifier(_) => {
@@ -190,117 +1137,491 @@ impl<R: Read> Parser<R> {
                     }
                     accept_more = false;
 
-                    let name = match self.lexer.next_token()? {
-                        Token::Identifier(id) => id,
-                        _ => unreachable!(),
-                    };
+                    let name = self.identifier()?;
                     self.eat(Token::Colon)?;
-                    let type_ =
-                        typing::get_const_str_from_string(match self.lexer.next_token()? {
-                            Token::Identifier(t) => t,
-                            _ => return Err(Error::Unexpected("type token")),
-                        });
+                    let type_ = self.type_()?;
                     params.push(Parameter { name, type_ });
                 }
+                Token::Ellipsis => {
+                    if !accept_more {
+                        return Err(Error::Unexpected("operator, expected ','"));
+                    }
+                    // Variadic marker must be the last parameter
+                    self.eat(Token::Ellipsis)?;
+                    self.eat(Token::CloseParen)?;
+                    variadic = true;
+                    break;
+                }
                 Token::CloseParen => {
                     self.eat(Token::CloseParen)?;
                     break;
                 }
                 Token::Comma => {
+                    if accept_more {
+                        return Err(Error::Unexpected("',', expected a parameter"));
+                    }
                     self.eat(Token::Comma)?;
                     accept_more = true;
                 }
-                x => {
-                    dbg!(x);
-                    return Err(Error::Unexpected("token"));
-                }
+                _ => return Err(Error::Unexpected("token")),
             }
         }
 
-        Ok(params)
+        Ok((params, variadic))
+    }
+
+    // A negative literal is negated before its range is checked, so `-128i8` fits. A `u128`
+    // past the range of an `i128` is held by its bits
+    fn integer(&mut self, negative: bool, start: Span) -> Result<Expr> {
+        let Token::Integer(magnitude, suffix) = self.lexer.next_token()? else {
+            return Err(Error::Unexpected("token, expecting an integer"));
+        };
+        let value = if negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        };
+        let type_ = match suffix {
+            Some(suffix) => match AuroraType::from_string(&suffix) {
+                Some(type_) if type_.is_integer() => type_,
+                _ => return Err(Error::InvalidLiteralSuffix(suffix).at(start)),
+            },
+            // Past the range of an `i64` it's an `i128`, and past that a `u128`, still narrowed
+            // to the type it's given to
+            None if value.is_some_and(|value| AuroraType::I64.fits(value)) => AuroraType::I64,
+            None if value.is_some() || negative => AuroraType::I128,
+            None => AuroraType::U128,
+        };
+        let value = match value {
+            Some(value) if type_.fits(value) => value,
+            None if type_ == AuroraType::U128 && !negative => magnitude as i128,
+            _ => {
+                let sign = if negative { "-" } else { "" };
+                let literal = format!("{}{}", sign, magnitude);
+                return Err(Error::IntegerLiteralOutOfRange(literal, type_).at(start));
+            }
+        };
+        Ok(Expr {
+            expr_type: ExprType::Integer(value),
+            type_,
+            span: self.span(start),
+        })
     }
 
     fn primary(&mut self) -> Result<Expr> {
+        let start = self.start()?;
+        if let Token::Str(_) = self.lexer.peek(0)? {
+            let Token::Str(string) = self.lexer.next_token()? else {
+                return Err(Error::Unexpected("token, expecting a string"));
+            };
+            return Ok(Expr {
+                expr_type: ExprType::Str(string),
+                type_: AuroraType::Str,
+                span: self.span(start),
+            });
+        }
         match *self.lexer.peek(0)? {
             Token::Float(f) => {
                 self.lexer.next_token()?;
                 Ok(Expr {
                     expr_type: ExprType::Float(f),
-                    type_: typing::F64,
+                    type_: AuroraType::F64,
+                    span: self.span(start),
                 })
             }
-            Token::Integer(i) => {
-                self.lexer.next_token()?;
+            Token::Integer(..) => self.integer(false, start),
+            Token::True | Token::False => {
+                let value = self.lexer.next_token()? == Token::True;
                 Ok(Expr {
-                    expr_type: ExprType::Integer(i),
-                    type_: typing::I64,
+                    expr_type: ExprType::Boolean(value),
+                    type_: AuroraType::Bool,
+                    span: self.span(start),
                 })
             }
             Token::OpenParen => {
                 self.eat(Token::OpenParen)?;
                 let expr = self.expr()?;
                 self.eat(Token::CloseParen)?;
-                Ok(expr)
+                self.postfix(expr)
             }
             Token::Identifier(_) => self.ident_expr(),
-            _ => Err(Error::Unexpected("token when expecting an expression")),
+            Token::Star => {
+                self.eat(Token::Star)?;
+                let pointer = self.primary()?;
+                let AuroraType::Ptr(pointee) = &pointer.type_ else {
+                    return Err(Error::NotDereferenceable(pointer.type_).at(pointer.span));
+                };
+                Ok(Expr {
+                    type_: (**pointee).clone(),
+                    expr_type: ExprType::Deref(Box::new(pointer)),
+                    span: self.span(start),
+                })
+            }
+            Token::Minus => {
+                self.eat(Token::Minus)?;
+                match *self.lexer.peek(0)? {
+                    Token::Integer(..) => return self.integer(true, start),
+                    Token::Float(value) => {
+                        self.lexer.next_token()?;
+                        return Ok(Expr {
+                            expr_type: ExprType::Float(-value),
+                            type_: AuroraType::F64,
+                            span: self.span(start),
+                        });
+                    }
+                    _ => (),
+                }
+                let operand = self.primary()?;
+                Ok(Expr {
+                    type_: operand.type_.clone(),
+                    expr_type: ExprType::Unary(UnaryOp::Negate, Box::new(operand)),
+                    span: self.span(start),
+                })
+            }
+            Token::Tilde => {
+                self.eat(Token::Tilde)?;
+                let operand = self.primary()?;
+                Ok(Expr {
+                    type_: operand.type_.clone(),
+                    expr_type: ExprType::Unary(UnaryOp::BitNot, Box::new(operand)),
+                    span: self.span(start),
+                })
+            }
+            Token::Ampersand => {
+                self.eat(Token::Ampersand)?;
+                let place = self.primary()?;
+                if !matches!(
+                    place.expr_type,
+                    ExprType::Variable(_) | ExprType::Index(..) | ExprType::Field(..)
+                ) {
+                    return Err(Error::Unexpected(
+                        "operand of '&', expecting a variable, an array element or a field",
+                    )
+                    .at(place.span));
+                }
+                Ok(Expr {
+                    type_: AuroraType::Ptr(Box::new(place.type_.clone())),
+                    expr_type: ExprType::AddressOf(Box::new(place)),
+                    span: self.span(start),
+                })
+            }
+            _ => Err(Error::Unexpected("token when expecting an expression").at(start)),
         }
     }
 
     fn ident_expr(&mut self) -> Result<Expr> {
+        let start = self.start()?;
         let name = self.identifier()?;
-        let type_ = match self.type_map.get(&name) {
-            Some(t) => *t,
-            None => return Err(Error::Undefined(format!("identifier {}", name))),
-        };
+        if let (Some(intrinsic), Token::OpenParen) =
+            (Intrinsic::from_name(name.as_str()), self.lexer.peek(0)?)
+        {
+            let ast = self.intrinsic(intrinsic, start)?;
+            return self.postfix(ast);
+        }
         let ast = match self.lexer.peek(0)? {
+            Token::OpenParen if name == "sizeof" => self.sizeof(start)?,
+            Token::OpenParen if name == "assert" => self.assert(start)?,
+            Token::OpenParen if name == "print" => self.print(start)?,
+            Token::OpenBracket
+                if matches!(self.types.get(name.as_str()), Some(AuroraType::Struct(..))) =>
+            {
+                self.struct_literal(name.as_str(), start)?
+            }
+            Token::Dot if matches!(self.types.get(name.as_str()), Some(AuroraType::Enum(..))) => {
+                self.variant(name.as_str(), start)?
+            }
             Token::OpenParen => {
                 self.eat(Token::OpenParen)?;
                 let args = self.args()?;
                 self.eat(Token::CloseParen)?;
+                let prototype = self.resolve(name, &args, start)?;
+                let args = Self::literal_args(args, prototype)?;
                 Expr {
-                    expr_type: ExprType::Call(name, args),
-                    type_,
+                    type_: prototype.return_type.clone(),
+                    expr_type: ExprType::Call(prototype.function_name, args),
+                    span: self.span(start),
                 }
             }
-            _ => Expr {
-                expr_type: ExprType::Variable(name),
-                type_,
+            _ => match self.lookup(name).cloned() {
+                Some(AuroraType::Any) if self.poisoned.contains(&name) => {
+                    return Err(Error::Poisoned)
+                }
+                Some(AuroraType::Any) => {
+                    return Err(Error::UnresolvedType(name.to_string()).at(start))
+                }
+                Some(type_) => {
+                    self.read(name);
+                    Expr {
+                        type_,
+                        expr_type: ExprType::Variable(name),
+                        span: self.span(start),
+                    }
+                }
+                // Reported where the name is rather than past it
+                None => {
+                    let error = Error::Undefined(format!("identifier {}", name));
+                    return Err(error.at(start));
+                }
             },
         };
-        Ok(ast)
+        self.postfix(ast)
+    }
+
+    // Indexing and field accesses following an expression, as in `points[0].x`
+    fn postfix(&mut self, mut expr: Expr) -> Result<Expr> {
+        loop {
+            expr = match self.lexer.peek(0)? {
+                Token::OpenSquare => self.index(expr)?,
+                Token::Dot => self.field(expr)?,
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    // `Name { field: value, ... }`, every field given once
+    fn struct_literal(&mut self, name: &str, start: Span) -> Result<Expr> {
+        let type_ = self.types[name].clone();
+        let AuroraType::Struct(_, fields) = &type_ else {
+            unreachable!()
+        };
+        self.eat(Token::OpenBracket)?;
+        let mut values: Vec<(String, Expr)> = vec![];
+        while *self.lexer.peek(0)? != Token::CloseBracket {
+            let name = self.identifier()?.to_string();
+            self.eat(Token::Colon)?;
+            let value = self.expr()?;
+            let Some((_, field_type)) = fields.iter().find(|(field, _)| *field == name) else {
+                return Err(Error::UnknownField(type_.clone(), name));
+            };
+            let value = Self::literal_as(value, field_type)?;
+            if value.type_ != *field_type {
+                return Err(Error::MismatchedTypes(field_type.clone(), value.type_));
+            }
+            if values.iter().any(|(given, _)| *given == name) {
+                return Err(Error::DuplicateField(name));
+            }
+            values.push((name, value));
+            if *self.lexer.peek(0)? != Token::CloseBracket {
+                self.eat(Token::Comma)?;
+            }
+        }
+        self.eat(Token::CloseBracket)?;
+
+        let missing = fields
+            .iter()
+            .find(|(field, _)| !values.iter().any(|(given, _)| given == field));
+        if let Some((field, _)) = missing {
+            return Err(Error::MissingField(type_.clone(), field.clone()));
+        }
+        Ok(Expr {
+            expr_type: ExprType::Struct(values),
+            type_,
+            span: self.span(start),
+        })
+    }
+
+    // `Name.Variant`, a constant holding the variant's index
+    fn variant(&mut self, name: &str, start: Span) -> Result<Expr> {
+        let type_ = self.types[name].clone();
+        let AuroraType::Enum(_, variants) = &type_ else {
+            unreachable!()
+        };
+        self.eat(Token::Dot)?;
+        let variant = self.identifier()?.to_string();
+        let Some(index) = variants.iter().position(|name| *name == variant) else {
+            return Err(Error::UnknownVariant(type_.clone(), variant));
+        };
+        Ok(Expr {
+            expr_type: ExprType::Integer(index as i128),
+            type_,
+            span: self.span(start),
+        })
+    }
+
+    fn field(&mut self, struct_: Expr) -> Result<Expr> {
+        self.eat(Token::Dot)?;
+        let name = self.identifier()?.to_string();
+        let AuroraType::Struct(_, fields) = &struct_.type_ else {
+            return Err(Error::NotAStruct(struct_.type_));
+        };
+        let Some((_, type_)) = fields.iter().find(|(field, _)| *field == name) else {
+            return Err(Error::UnknownField(struct_.type_.clone(), name));
+        };
+        Ok(Expr {
+            type_: type_.clone(),
+            span: self.span(struct_.span),
+            expr_type: ExprType::Field(Box::new(struct_), name),
+        })
+    }
+
+    fn sizeof(&mut self, start: Span) -> Result<Expr> {
+        self.eat(Token::OpenParen)?;
+        let type_ = self.type_()?;
+        self.eat(Token::CloseParen)?;
+        Ok(Expr {
+            type_: AuroraType::I64,
+            expr_type: ExprType::SizeOf(type_),
+            span: self.span(start),
+        })
+    }
+
+    // `assert(condition)` or `assert(condition, "message")`
+    fn assert(&mut self, start: Span) -> Result<Expr> {
+        self.eat(Token::OpenParen)?;
+        let condition = self.expr()?;
+        let message = if *self.lexer.peek(0)? == Token::Comma {
+            self.eat(Token::Comma)?;
+            match self.lexer.next_token()? {
+                Token::Str(message) => Some(message),
+                _ => {
+                    return Err(Error::Unexpected(
+                        "token, expecting the message of an assert",
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+        self.eat(Token::CloseParen)?;
+        Ok(Expr {
+            type_: AuroraType::Void,
+            expr_type: ExprType::Assert(Box::new(condition), message),
+            span: self.span(start),
+        })
+    }
+
+    // `print(value)`, calling the runtime function for the value's type
+    fn print(&mut self, start: Span) -> Result<Expr> {
+        self.eat(Token::OpenParen)?;
+        let value = self.expr()?;
+        self.eat(Token::CloseParen)?;
+        let Some(printer) = Self::printer(&value.type_) else {
+            return Err(Error::NotPrintable(value.type_));
+        };
+        let mut call = Self::print_call(printer, value);
+        call.span = self.span(start);
+        Ok(call)
+    }
+
+    // `wrapping_add(a, b)` and the other intrinsics, a literal operand taking the type of
+    // the other one
+    fn intrinsic(&mut self, intrinsic: Intrinsic, start: Span) -> Result<Expr> {
+        self.eat(Token::OpenParen)?;
+        let left = self.expr()?;
+        self.eat(Token::Comma)?;
+        let right = self.expr()?;
+        self.eat(Token::CloseParen)?;
+        let left = Self::literal_as(left, &right.type_)?;
+        let right = Self::literal_as(right, &left.type_)?;
+        Ok(Expr {
+            type_: left.type_.clone(),
+            span: self.span(start),
+            expr_type: ExprType::Intrinsic(intrinsic, Box::new(left), Box::new(right)),
+        })
+    }
+
+    fn index(&mut self, array: Expr) -> Result<Expr> {
+        self.eat(Token::OpenSquare)?;
+        let index = self.expr()?;
+        self.eat(Token::CloseSquare)?;
+        let AuroraType::Array(element, _) = &array.type_ else {
+            return Err(Error::NotIndexable(array.type_));
+        };
+        if !index.type_.is_integer() {
+            return Err(Error::MismatchedTypes(AuroraType::I64, index.type_));
+        }
+        Ok(Expr {
+            type_: (**element).clone(),
+            span: self.span(array.span),
+            expr_type: ExprType::Index(Box::new(array), Box::new(index)),
+        })
     }
 
-    fn args(&mut self) -> Result<Vec<Expr>> {
+    fn args(&mut self) -> Result<Vec<Argument>> {
         if *self.lexer.peek(0)? == Token::CloseParen {
             return Ok(vec![]);
         }
-        let mut args = vec![self.expr()?];
+        let mut args = vec![self.argument()?];
         while *self.lexer.peek(0)? == Token::Comma {
             self.eat(Token::Comma)?;
-            args.push(self.expr()?);
+            let arg = self.argument()?;
+            if arg.name.is_none() && args.last().is_some_and(|arg| arg.name.is_some()) {
+                return Err(Error::Unexpected("positional argument after a named one"));
+            }
+            args.push(arg);
         }
 
         Ok(args)
     }
 
+    // `value` or `name: value`
+    fn argument(&mut self) -> Result<Argument> {
+        let named = matches!(self.lexer.peek(0)?, Token::Identifier(_))
+            && *self.lexer.peek(1)? == Token::Colon;
+        let name = if named {
+            let name = self.identifier()?;
+            self.eat(Token::Colon)?;
+            Some(name)
+        } else {
+            None
+        };
+        Ok(Argument {
+            name,
+            value: self.expr()?,
+        })
+    }
+
     fn expr(&mut self) -> Result<Expr> {
-        let left = self.primary()?;
+        let left = self.operand()?;
         self.binary_right(0, left)
     }
 
+    // Operand of a binary operator, `as` binding tighter than any of them
+    fn operand(&mut self) -> Result<Expr> {
+        let mut operand = self.primary()?;
+        while *self.lexer.peek(0)? == Token::As {
+            operand = self.cast(operand)?;
+        }
+        Ok(operand)
+    }
+
+    // `value as type`, between numbers and from or to a `bool`. An integer is true when it
+    // isn't 0. Numbers only convert to types holding all of their values, as a literal
+    // fitting the type does
+    fn cast(&mut self, value: Expr) -> Result<Expr> {
+        self.eat(Token::As)?;
+        let type_ = self.type_()?;
+        let value = Self::literal_as(value, &type_)?;
+        let from = &value.type_;
+        if *from == type_ {
+            return Ok(value);
+        }
+        let converts = (*from == AuroraType::Bool && type_.is_integer())
+            || (from.is_integer() && type_ == AuroraType::Bool)
+            || from.promote(&type_).as_ref() == Some(&type_);
+        if !converts {
+            let span = self.span(value.span);
+            return Err(Error::InvalidCast(value.type_, type_).at(span));
+        }
+        Ok(Expr {
+            type_,
+            span: self.span(value.span),
+            expr_type: ExprType::Cast(Box::new(value)),
+        })
+    }
+
     fn binary_right(&mut self, expr_precedence: i32, left: Expr) -> Result<Expr> {
         match self.binary_op()? {
             Some(op) => {
-                let token_precedence = self.precedence(op)?;
+                let token_precedence = op.precedence();
                 if token_precedence < expr_precedence {
                     Ok(left)
                 } else {
                     self.lexer.next_token()?;
-                    let right = self.primary()?;
+                    let right = self.operand()?;
                     let right = match self.binary_op()? {
                         Some(op) => {
-                            if token_precedence < self.precedence(op)? {
+                            if token_precedence < op.precedence() {
                                 self.binary_right(token_precedence + 1, right)?
                             } else {
                                 right
@@ -308,10 +1629,29 @@ impl<R: Read> Parser<R> {
                         }
                         None => right,
                     };
+                    // Comparisons are `bool` even though codegen gives them an `i8`, so they
+                    // can be returned from and assigned to `bool`s as is
+                    let type_ = if op.is_comparison() || op.is_logical() {
+                        AuroraType::Bool
+                    } else if op.is_shift() {
+                        left.type_.clone()
+                    } else {
+                        left.type_
+                            .promote(&right.type_)
+                            .unwrap_or(left.type_.clone())
+                    };
                     let left = Expr {
-                        type_: left.type_,
+                        type_,
+                        span: self.span(left.span),
                         expr_type: ExprType::Binary(op, Box::new(left), Box::new(right)),
                     };
+                    if op.is_comparison() {
+                        if let Some(next) = self.binary_op()? {
+                            if next.is_comparison() {
+                                return Err(Error::ChainedComparison);
+                            }
+                        }
+                    }
                     self.binary_right(expr_precedence, left)
                 }
             }
@@ -321,19 +1661,26 @@ impl<R: Read> Parser<R> {
 
     fn binary_op(&mut self) -> Result<Option<BinaryOp>> {
         let op = match self.lexer.peek(0)? {
+            Token::AndAnd => BinaryOp::And,
+            Token::OrOr => BinaryOp::Or,
             Token::LessThan => BinaryOp::LessThan,
+            Token::LessEqual => BinaryOp::LessEqual,
+            Token::GreaterThan => BinaryOp::GreaterThan,
+            Token::GreaterEqual => BinaryOp::GreaterEqual,
+            Token::EqualEqual => BinaryOp::Equal,
+            Token::BangEqual => BinaryOp::NotEqual,
             Token::Minus => BinaryOp::Minus,
             Token::Plus => BinaryOp::Plus,
             Token::Star => BinaryOp::Times,
+            Token::Slash => BinaryOp::Divide,
+            Token::Percent => BinaryOp::Modulo,
+            Token::Ampersand => BinaryOp::BitAnd,
+            Token::Pipe => BinaryOp::BitOr,
+            Token::Caret => BinaryOp::BitXor,
+            Token::ShiftLeft => BinaryOp::ShiftLeft,
+            Token::ShiftRight => BinaryOp::ShiftRight,
             _ => return Ok(None),
         };
         Ok(Some(op))
     }
-
-    fn precedence(&mut self, op: BinaryOp) -> Result<i32> {
-        match self.bin_precedence.get(&op) {
-            Some(&precedence) => Ok(precedence),
-            None => Err(Error::Undefined("operator".to_string())),
-        }
-    }
 }
@@ -0,0 +1,130 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, Output, Stdio},
+};
+
+use crate::{
+    compile,
+    error::{Error, Result},
+    link::Linker,
+    options::Options,
+};
+
+// Keywords starting a definition, which is kept for the lines after it
+const DEFINITIONS: [&str; 5] = ["fn", "extern", "struct", "enum", "import"];
+
+// A compile-and-run loop reading lines from stdin until it ends. A definition is checked and
+// kept for the following lines, anything else is compiled along with them into a program
+// that is linked and run as a process of its own, printing the value of an expression.
+// Nothing is JIT compiled, so no state outlives a line: a statement that ran is kept in the
+// source and computed again by every later line. What it printed is only shown once, but
+// other side effects of it happen again
+pub fn run(options: &Options) -> Result<()> {
+    let directory = TempDir::new()?;
+    let mut options = options.clone();
+    options.output = directory.0.join("line").to_string_lossy().into_owned();
+    options.compile.source_name = "<repl>".to_string();
+    // Bindings are kept for the lines after them
+    options.compile.warn_unused = false;
+
+    let renderer = options.compile.renderer();
+    let mut definitions = String::new();
+    // Length of the output of the `let`s kept, which every later program prints first
+    let mut printed = 0;
+    prompt()?;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        match line.split_whitespace().next() {
+            None => (),
+            Some(keyword) if DEFINITIONS.contains(&keyword) => {
+                let source = format!("{}{}\n", definitions, line);
+                let mut compile_options = options.compile.clone();
+                compile_options.entry = None;
+                match compile(source.as_bytes(), compile_options) {
+                    Ok(_) => definitions = source,
+                    Err(error) => error.report(renderer.as_ref()),
+                }
+            }
+            Some(_) => {
+                // The semicolon of a lone statement is optional
+                let terminator = if line.ends_with(';') || line.ends_with('}') {
+                    ""
+                } else {
+                    ";"
+                };
+                let source = format!("{}{}{}\n", definitions, line, terminator);
+                match evaluate(&source, &options, true) {
+                    Ok(output) => {
+                        io::stdout().write_all(output.stdout.get(printed..).unwrap_or_default())?;
+                        if output.status.success() {
+                            // What the statement printed, without its value, is printed
+                            // again first by every later program
+                            match evaluate(&source, &options, false) {
+                                Ok(kept) if kept.status.success() => {
+                                    definitions = source;
+                                    printed = kept.stdout.len();
+                                }
+                                Ok(kept) => failed(&kept).report(renderer.as_ref()),
+                                Err(error) => error.report(renderer.as_ref()),
+                            }
+                        } else {
+                            failed(&output).report(renderer.as_ref());
+                        }
+                    }
+                    Err(error) => error.report(renderer.as_ref()),
+                }
+            }
+        }
+        prompt()?;
+    }
+    println!();
+    Ok(())
+}
+
+fn prompt() -> Result<()> {
+    print!("> ");
+    io::stdout().flush()?;
+    Ok(())
+}
+
+// A directory of the process's own for the programs it builds, removed once it's done
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Result<Self> {
+        let path = env::temp_dir().join(format!("aurora-repl-{}", process::id()));
+        fs::create_dir_all(&path).map_err(Error::io_at(&path))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Builds and runs a program, returning how it ended and what it printed to stdout, along
+// with the value of its last statement when asked to
+fn evaluate(source: &str, options: &Options, print_result: bool) -> Result<Output> {
+    let mut compile_options = options.compile.clone();
+    compile_options.entry = Some("main".to_string());
+    compile_options.print_result = print_result;
+    let object = compile(source.as_bytes(), compile_options)?;
+
+    let object_filename = Path::new(&options.output).with_extension("o");
+    fs::write(&object_filename, object).map_err(Error::io_at(&object_filename))?;
+    Linker::new(options).link(&object_filename.to_string_lossy())?;
+    let output = Command::new(&options.output)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(Error::io_at(&options.output))?;
+    Ok(output)
+}
+
+fn failed(output: &Output) -> Error {
+    Error::ProgramFailed(output.status.to_string())
+}
@@ -0,0 +1,38 @@
+use crate::{
+    ast::{Parameter, Prototype},
+    intern::Symbol,
+    typing::AuroraType,
+};
+
+// Every symbol declared here must be defined in `SOURCE`, which is linked into
+// every executable, so programs can call them without an `extern`.
+pub const SOURCE: &str = include_str!("../runtime/runtime.c");
+
+pub fn prototypes() -> Vec<Prototype> {
+    vec![
+        prototype("putfloatd", &[("f", AuroraType::F64)], AuroraType::Void),
+        prototype("putint", &[("i", AuroraType::I64)], AuroraType::Void),
+        prototype("putbool", &[("b", AuroraType::Bool)], AuroraType::Void),
+        // Reports the message of a failed `assert` before it traps
+        prototype(
+            "aurora_assert_failed",
+            &[("message", AuroraType::Str)],
+            AuroraType::Void,
+        ),
+    ]
+}
+
+fn prototype(name: &str, parameters: &[(&str, AuroraType)], return_type: AuroraType) -> Prototype {
+    Prototype {
+        function_name: Symbol::intern(name),
+        parameters: parameters
+            .iter()
+            .map(|(name, type_)| Parameter {
+                name: Symbol::intern(name),
+                type_: type_.clone(),
+            })
+            .collect(),
+        return_type,
+        variadic: false,
+    }
+}
@@ -0,0 +1,43 @@
+use std::{cell::RefCell, collections::HashMap, fs};
+
+// Lines of the files diagnostics point into. The lexer doesn't keep what it read, so a file is
+// read again the first time one of its lines is needed. Sources that aren't files, like the
+// REPL's, have none
+#[derive(Default)]
+pub struct SourceMap {
+    files: RefCell<HashMap<String, Option<Vec<String>>>>,
+}
+
+impl SourceMap {
+    // A line starting at 1
+    pub fn line(&self, file: &str, line: usize) -> Option<String> {
+        let mut files = self.files.borrow_mut();
+        let lines = files.entry(file.to_string()).or_insert_with(|| {
+            fs::read_to_string(file)
+                .ok()
+                .map(|source| source.lines().map(str::to_string).collect())
+        });
+        lines.as_ref()?.get(line.checked_sub(1)?).cloned()
+    }
+
+    // The line prefixed with its number, and a caret under the column when there's one:
+    //
+    //    3 | let x = y;
+    //      |         ^
+    pub fn snippet(&self, file: &str, line: usize, col: Option<usize>) -> Option<String> {
+        let text = self.line(file, line)?;
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len().max(4));
+        let mut snippet = format!("{:>4} | {}", number, text);
+        if let Some(col) = col {
+            // Columns count bytes, and tabs are kept so the caret lines up
+            let indent: String = text
+                .char_indices()
+                .take_while(|(offset, _)| offset + 1 < col)
+                .map(|(_, char)| if char == '\t' { '\t' } else { ' ' })
+                .collect();
+            snippet.push_str(&format!("\n{} | {}^", gutter, indent));
+        }
+        Some(snippet)
+    }
+}
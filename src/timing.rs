@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+// Wall-clock time spent in each pass of a compilation, in the order they first ran
+#[derive(Default)]
+pub struct PassTimes {
+    passes: Vec<(&'static str, Duration)>,
+}
+
+impl PassTimes {
+    // Adds to the time of a pass, which may run several times, e.g. once per function
+    pub fn add(&mut self, pass: &'static str, time: Duration) {
+        match self.passes.iter_mut().find(|(name, _)| *name == pass) {
+            Some((_, total)) => *total += time,
+            None => self.passes.push((pass, time)),
+        }
+    }
+
+    pub fn time<T>(&mut self, pass: &'static str, run: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = run();
+        self.add(pass, start.elapsed());
+        result
+    }
+
+    // Prints each pass with its share of the total to stderr
+    pub fn print(&self) {
+        let total: Duration = self.passes.iter().map(|(_, time)| *time).sum();
+        for (pass, time) in &self.passes {
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            eprintln!(
+                "{:<20} {:>10.3}ms {:>5.1}%",
+                pass,
+                time.as_secs_f64() * 1000.0,
+                share
+            );
+        }
+        eprintln!("{:<20} {:>10.3}ms", "total", total.as_secs_f64() * 1000.0);
+    }
+}
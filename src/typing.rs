@@ -1,41 +1,633 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    mem,
+};
+
 use cranelift_codegen::ir::{types, Type};
 
-pub fn get_type_from_str(str: &str) -> Option<Type> {
-    match str {
-        I8 => Some(types::I8),
-        I16 => Some(types::I16),
-        I32 => Some(types::I32),
-        I64 => Some(types::I64),
-        F32 => Some(types::F32),
-        F64 => Some(types::F64),
-        VOID => None,
-        _ => None, // TODO: Trigger error
+use crate::{
+    ast::{Argument, BinaryOp, Expr, ExprType, Function, Prototype, UnaryOp},
+    error::{Error, Result},
+    intern::Symbol,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AuroraType {
+    Any,
+    Void,
+    Bool,
+    Str,
+
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+
+    F32,
+    F64,
+
+    // Element type and length
+    Array(Box<AuroraType>, usize),
+    Ptr(Box<AuroraType>),
+    // Name and fields, laid out in order with each field aligned to its own alignment
+    Struct(String, Vec<(String, AuroraType)>),
+    // Name and variants, each held as its index
+    Enum(String, Vec<String>),
+}
+
+impl AuroraType {
+    pub fn from_string(str: &str) -> Option<Self> {
+        match str {
+            "void" => Some(Self::Void),
+            "bool" => Some(Self::Bool),
+            "str" => Some(Self::Str),
+
+            "i8" => Some(Self::I8),
+            "i16" => Some(Self::I16),
+            "i32" => Some(Self::I32),
+            "i64" => Some(Self::I64),
+            "i128" => Some(Self::I128),
+
+            "u8" => Some(Self::U8),
+            "u16" => Some(Self::U16),
+            "u32" => Some(Self::U32),
+            "u64" => Some(Self::U64),
+            "u128" => Some(Self::U128),
+
+            "f32" => Some(Self::F32),
+            "f64" => Some(Self::F64),
+
+            _ => None,
+        }
+    }
+
+    pub fn get_type(&self, pointer_type: Type) -> Option<Type> {
+        match self {
+            // Comparisons in cranelift yield an i8 holding 0 or 1. Every bool is kept that way,
+            // literals, `&&`/`||` results, locals and fields alike, so one can be branched on,
+            // compared or stored without converting it. Only C's variadic arguments widen it,
+            // zero-extended to an `int`
+            Self::Bool => Some(types::I8),
+            // Pointer to a nul-terminated string
+            Self::Str => Some(pointer_type),
+
+            Self::I8 => Some(types::I8),
+            Self::I16 => Some(types::I16),
+            Self::I32 => Some(types::I32),
+            Self::I64 => Some(types::I64),
+            Self::I128 => Some(types::I128),
+
+            Self::U8 => Some(types::I8),
+            Self::U16 => Some(types::I16),
+            Self::U32 => Some(types::I32),
+            Self::U64 => Some(types::I64),
+            Self::U128 => Some(types::I128),
+
+            Self::F32 => Some(types::F32),
+            Self::F64 => Some(types::F64),
+
+            // Pointer to the array's stack slot
+            Self::Array(..) => Some(pointer_type),
+            Self::Ptr(_) => Some(pointer_type),
+            // Pointer to the struct's stack slot
+            Self::Struct(..) => Some(pointer_type),
+            Self::Enum(..) => Some(types::I32),
+
+            Self::Any | Self::Void => None,
+        }
+    }
+
+    // Size in bytes of a value of this type, arrays being stored inline
+    pub fn size(&self, pointer_type: Type) -> u32 {
+        match self {
+            Self::Array(element, length) => element.size(pointer_type) * *length as u32,
+            Self::Struct(_, fields) => {
+                let end = match fields.last() {
+                    Some((name, type_)) => {
+                        self.field(name, pointer_type).unwrap().0 + type_.size(pointer_type)
+                    }
+                    None => 0,
+                };
+                end.next_multiple_of(self.align(pointer_type))
+            }
+            _ => self.get_type(pointer_type).map_or(0, |type_| type_.bytes()),
+        }
+    }
+
+    // Alignment in bytes of a value of this type stored in memory
+    pub fn align(&self, pointer_type: Type) -> u32 {
+        match self {
+            Self::Array(element, _) => element.align(pointer_type),
+            Self::Struct(_, fields) => fields
+                .iter()
+                .map(|(_, type_)| type_.align(pointer_type))
+                .max()
+                .unwrap_or(1),
+            _ => self.size(pointer_type).max(1),
+        }
+    }
+
+    // Byte offset and type of a struct's field
+    pub fn field(&self, name: &str, pointer_type: Type) -> Option<(u32, &AuroraType)> {
+        let Self::Struct(_, fields) = self else {
+            return None;
+        };
+        let mut offset: u32 = 0;
+        for (field, type_) in fields {
+            offset = offset.next_multiple_of(type_.align(pointer_type));
+            if field == name {
+                return Some((offset, type_));
+            }
+            offset += type_.size(pointer_type);
+        }
+        None
+    }
+
+    // Arrays and structs are held by the address of their memory instead of by value
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self, Self::Array(..) | Self::Struct(..))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_signed() || self.is_unsigned()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Self::I8 | Self::I16 | Self::I32 | Self::I64 | Self::I128
+        )
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 | Self::U128
+        )
+    }
+
+    // Whether an integer value is representable by this type
+    pub fn fits(&self, value: i128) -> bool {
+        match self {
+            Self::I8 => i8::try_from(value).is_ok(),
+            Self::I16 => i16::try_from(value).is_ok(),
+            Self::I32 => i32::try_from(value).is_ok(),
+            Self::I64 => i64::try_from(value).is_ok(),
+            Self::I128 => true,
+
+            Self::U8 => u8::try_from(value).is_ok(),
+            Self::U16 => u16::try_from(value).is_ok(),
+            Self::U32 => u32::try_from(value).is_ok(),
+            Self::U64 => u64::try_from(value).is_ok(),
+            Self::U128 => value >= 0,
+
+            _ => false,
+        }
+    }
+
+    // Whether an integer literal of the other type is representable by this one
+    pub fn fits_literal(&self, value: i128, literal_type: &Self) -> bool {
+        match literal_type {
+            Self::U128 if value < 0 => *self == Self::U128,
+            _ => self.fits(value),
+        }
+    }
+
+    // Text of an integer literal of this type. A `u128` past the range of an `i128` is held
+    // by its bits
+    pub fn literal(&self, value: i128) -> String {
+        match self {
+            Self::U128 => (value as u128).to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+
+    // Common type the operands of a binary operator are converted to. Integers widen to the
+    // wider of the two and mixing in a float gives the float type
+    pub fn promote(&self, other: &Self) -> Option<Self> {
+        if self == other {
+            return Some(self.clone());
+        }
+        let (bits, other_bits) = (self.numeric_bits()?, other.numeric_bits()?);
+        let wider = if bits > other_bits { self } else { other };
+        match (self.is_float(), other.is_float()) {
+            (true, false) => Some(self.clone()),
+            (false, true) => Some(other.clone()),
+            // Same width but different signedness has no lossless common type
+            _ if bits == other_bits => None,
+            _ => Some(wider.clone()),
+        }
+    }
+
+    // Whether C implicitly converts a value of this type to the other when passed as an
+    // argument, integers widening to integers and floats to floats
+    pub fn c_widens_to(&self, other: &Self) -> bool {
+        self.is_float() == other.is_float() && self.promote(other).as_ref() == Some(other)
+    }
+
+    // Type C passes a variadic argument of this type as, integers narrower than an `int`
+    // widening to one and `float` to `double`
+    pub fn c_variadic(&self) -> Self {
+        match self {
+            Self::Bool | Self::I8 | Self::I16 | Self::U8 | Self::U16 => Self::I32,
+            Self::F32 => Self::F64,
+            _ => self.clone(),
+        }
+    }
+
+    fn numeric_bits(&self) -> Option<u32> {
+        match self {
+            Self::I8 | Self::U8 => Some(8),
+            Self::I16 | Self::U16 => Some(16),
+            Self::I32 | Self::U32 | Self::F32 => Some(32),
+            Self::I64 | Self::U64 | Self::F64 => Some(64),
+            Self::I128 | Self::U128 => Some(128),
+            _ => None,
+        }
     }
 }
 
-pub fn get_const_str_from_string(str: String) -> &'static str {
-    match str.as_str() {
-        VOID => VOID,
+impl Display for AuroraType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Void => write!(f, "void"),
+            Self::Bool => write!(f, "bool"),
+            Self::Str => write!(f, "str"),
+
+            Self::I8 => write!(f, "i8"),
+            Self::I16 => write!(f, "i16"),
+            Self::I32 => write!(f, "i32"),
+            Self::I64 => write!(f, "i64"),
+            Self::I128 => write!(f, "i128"),
+
+            Self::U8 => write!(f, "u8"),
+            Self::U16 => write!(f, "u16"),
+            Self::U32 => write!(f, "u32"),
+            Self::U64 => write!(f, "u64"),
+            Self::U128 => write!(f, "u128"),
+
+            Self::F32 => write!(f, "f32"),
+            Self::F64 => write!(f, "f64"),
+
+            Self::Array(element, length) => write!(f, "[{}; {}]", element, length),
+            Self::Ptr(pointee) => write!(f, "*{}", pointee),
+            Self::Struct(name, _) | Self::Enum(name, _) => write!(f, "{}", name),
+        }
+    }
+}
+
+// What calls need to know of a declared function
+#[derive(Clone, Debug)]
+pub struct Signature {
+    pub param_names: Vec<Symbol>,
+    pub param_types: Vec<AuroraType>,
+    pub return_type: AuroraType,
+    pub variadic: bool,
+    // Defined in C, so arguments follow its conversion rules
+    pub external: bool,
+}
+
+impl Signature {
+    pub fn new(prototype: &Prototype, external: bool) -> Self {
+        Self {
+            param_names: prototype
+                .parameters
+                .iter()
+                .map(|parameter| parameter.name)
+                .collect(),
+            param_types: prototype.param_types(),
+            return_type: prototype.return_type.clone(),
+            variadic: prototype.variadic,
+            external,
+        }
+    }
+
+    // Moves named arguments to the position of the parameter they name, positional ones
+    // coming before them keep theirs
+    fn order_arguments(&self, args: &mut Vec<Argument>) -> Result<()> {
+        let mut ordered = Vec::new();
+        ordered.resize_with(self.param_names.len().max(args.len()), || None);
+        for (i, arg) in mem::take(args).into_iter().enumerate() {
+            let position = match arg.name {
+                Some(name) => self
+                    .param_names
+                    .iter()
+                    .position(|param| *param == name)
+                    .ok_or_else(|| Error::UnknownParameter(name.to_string()))?,
+                None => i,
+            };
+            if ordered[position].is_some() {
+                return Err(Error::DuplicateArgument(
+                    self.param_names[position].to_string(),
+                ));
+            }
+            ordered[position] = Some(arg);
+        }
+        // A gap is a parameter left without an argument
+        *args = ordered
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or(Error::WrongArgumentCount)?;
+        Ok(())
+    }
+}
 
-        I8 => I8,
-        I16 => I16,
-        I32 => I32,
-        I64 => I64,
+// Checks the types of a function's body ahead of codegen, reporting every mistyped
+// expression instead of stopping at the first one. The arguments of calls are put in the
+// order of the callee's parameters, so codegen can take the body as well typed
+pub fn check<'a>(
+    function: &mut Function,
+    signatures: impl Fn(Symbol) -> Option<&'a Signature>,
+) -> Result<()> {
+    let mut checker = Checker {
+        name: function.prototype.function_name,
+        // Not declared yet when the function is compiled without its declarations
+        own: Signature::new(&function.prototype, false),
+        signatures: &signatures,
+        errors: vec![],
+    };
+    checker.expr(&mut function.body);
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::from_vec(checker.errors))
+    }
+}
 
-        F32 => F32,
-        F64 => F64,
+// Prints the signature of a function and the resolved type of each of its bindings, in
+// the order they're declared
+pub fn explain(function: &Function) {
+    let prototype = &function.prototype;
+    let mut parameters: Vec<String> = prototype
+        .parameters
+        .iter()
+        .map(|parameter| format!("{}: {}", parameter.name, parameter.type_))
+        .collect();
+    if prototype.variadic {
+        parameters.push("...".to_string());
+    }
+    let return_type = match prototype.return_type {
+        AuroraType::Void => String::new(),
+        ref type_ => format!(" {}", type_),
+    };
+    println!(
+        "fn {}({}){}",
+        prototype.function_name,
+        parameters.join(", "),
+        return_type
+    );
+    explain_lets(&function.body);
+}
 
-        _ => unimplemented!(),
+// Bindings are statements, so only the expressions holding blocks can have any
+fn explain_lets(expr: &Expr) {
+    match &expr.expr_type {
+        ExprType::Let(name, mutable, _) => {
+            let mutable = if *mutable { "mut " } else { "" };
+            println!("    let {}{}: {}", mutable, name, expr.type_);
+        }
+        ExprType::Block(exprs) => exprs.iter().for_each(explain_lets),
+        ExprType::IfElse(_, then, else_) => {
+            explain_lets(then);
+            if let Some(else_) = else_ {
+                explain_lets(else_);
+            }
+        }
+        ExprType::While(_, body, _) | ExprType::DoWhile(body, _, _) | ExprType::Loop(body, _) => {
+            explain_lets(body)
+        }
+        ExprType::Match(_, arms, default) => {
+            arms.iter().for_each(|(_, body)| explain_lets(body));
+            explain_lets(default);
+        }
+        _ => {}
     }
 }
 
-pub const ANY: &str = "";
-pub const VOID: &str = "void";
+struct Checker<'a, 's> {
+    name: Symbol,
+    own: Signature,
+    signatures: &'s dyn Fn(Symbol) -> Option<&'a Signature>,
+    errors: Vec<Error>,
+}
 
-pub const I8: &str = "i8";
-pub const I16: &str = "i16";
-pub const I32: &str = "i32";
-pub const I64: &str = "i64";
+impl Checker<'_, '_> {
+    // Checks the operands of an expression before the expression itself
+    fn expr(&mut self, expr: &mut Expr) {
+        match &mut expr.expr_type {
+            ExprType::Binary(_, left, right)
+            | ExprType::Intrinsic(_, left, right)
+            | ExprType::Index(left, right)
+            | ExprType::AssignDeref(left, right) => {
+                self.value(left);
+                self.value(right);
+            }
+            ExprType::Unary(_, value)
+            | ExprType::Cast(value)
+            | ExprType::Deref(value)
+            | ExprType::Field(value, _)
+            | ExprType::Assign(_, value)
+            | ExprType::Let(_, _, Some(value))
+            | ExprType::Return(Some(value)) => self.value(value),
+            ExprType::AddressOf(place) => self.expr(place),
+            ExprType::Call(name, args) => {
+                args.iter_mut().for_each(|arg| self.value(&mut arg.value));
+                if let Err(error) = self.call(*name, args) {
+                    self.errors.push(error.at(expr.span));
+                }
+            }
+            ExprType::Struct(values) => values.iter_mut().for_each(|(_, value)| self.value(value)),
+            ExprType::AssignIndex(array, index, value) => {
+                self.value(array);
+                self.value(index);
+                self.value(value);
+            }
+            ExprType::Assert(condition, _) => self.value(condition),
+            ExprType::Block(exprs) => exprs.iter_mut().for_each(|expr| self.expr(expr)),
+            ExprType::IfElse(condition, then, else_) => {
+                self.value(condition);
+                self.expr(then);
+                if let Some(else_) = else_ {
+                    self.expr(else_);
+                }
+            }
+            ExprType::While(condition, body, _) => {
+                self.value(condition);
+                self.expr(body);
+            }
+            ExprType::DoWhile(body, condition, _) => {
+                self.expr(body);
+                self.value(condition);
+            }
+            ExprType::Loop(body, _) => self.expr(body),
+            ExprType::Match(value, arms, default) => {
+                self.value(value);
+                arms.iter_mut().for_each(|(_, body)| self.expr(body));
+                self.expr(default);
+            }
+            ExprType::Integer(_)
+            | ExprType::Float(_)
+            | ExprType::Boolean(_)
+            | ExprType::Str(_)
+            | ExprType::Variable(_)
+            | ExprType::SizeOf(_)
+            | ExprType::Let(_, _, None)
+            | ExprType::Break(_)
+            | ExprType::Continue(_)
+            | ExprType::Return(None) => {}
+        }
+        if let Err(error) = self.check(expr) {
+            self.errors.push(error.at(expr.span));
+        }
+    }
 
-pub const F32: &str = "f32";
-pub const F64: &str = "f64";
+    // An expression whose value is used, which a call to a `void` function doesn't have
+    fn value(&mut self, expr: &mut Expr) {
+        if let (ExprType::Call(name, _), AuroraType::Void) = (&expr.expr_type, &expr.type_) {
+            self.errors
+                .push(Error::VoidValueUsed(name.to_string()).at(expr.span));
+            return;
+        }
+        self.expr(expr);
+    }
+
+    // Calls of functions that aren't declared are left for codegen to report
+    fn signature(&self, name: Symbol) -> Option<&Signature> {
+        (self.signatures)(name).or((name == self.name).then_some(&self.own))
+    }
+
+    // C functions take narrower arguments than their parameters, the others exactly their
+    // types
+    fn call(&self, name: Symbol, args: &mut Vec<Argument>) -> Result<()> {
+        let Some(signature) = self.signature(name) else {
+            return Ok(());
+        };
+        signature.order_arguments(args)?;
+        let param_count = signature.param_types.len();
+        if param_count != args.len() && !(signature.variadic && param_count < args.len()) {
+            return Err(Error::WrongArgumentCount);
+        }
+        for (arg, param_type) in args.iter().zip(&signature.param_types) {
+            let type_ = &arg.value.type_;
+            let accepted = if signature.external {
+                type_.c_widens_to(param_type)
+            } else {
+                type_ == param_type
+            };
+            if !accepted {
+                return Err(Error::MismatchedTypes(param_type.clone(), type_.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, expr: &Expr) -> Result<()> {
+        match &expr.expr_type {
+            ExprType::Binary(op, left, right) => binary(*op, &left.type_, &right.type_),
+            ExprType::Intrinsic(_, left, _) if !left.type_.is_integer() => {
+                Err(Error::Unexpected("integer intrinsic on this type"))
+            }
+            ExprType::Intrinsic(_, left, right) if left.type_ != right.type_ => Err(
+                Error::MismatchedTypes(left.type_.clone(), right.type_.clone()),
+            ),
+            ExprType::Unary(UnaryOp::BitNot, operand) if !operand.type_.is_integer() => {
+                Err(Error::Unexpected("bitwise operation on this type"))
+            }
+            ExprType::Unary(UnaryOp::Negate, operand)
+                if !operand.type_.is_float() && !operand.type_.is_signed() =>
+            {
+                Err(Error::Unexpected("negation of this type"))
+            }
+            ExprType::IfElse(condition, ..)
+            | ExprType::While(condition, ..)
+            | ExprType::DoWhile(_, condition, _)
+            | ExprType::Assert(condition, _)
+                if condition.type_ != AuroraType::Bool =>
+            {
+                Err(Error::NonBoolCondition(condition.type_.clone()))
+            }
+            ExprType::Return(Some(value)) => self.return_value(value),
+            ExprType::Return(None) if self.own.return_type != AuroraType::Void => {
+                Err(Error::MissingReturnValue(self.own.return_type.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Literals and narrower numbers are converted to the declared return type
+    fn return_value(&self, value: &Expr) -> Result<()> {
+        let return_type = &self.own.return_type;
+        if *return_type == AuroraType::Void {
+            return Err(Error::UnexpectedReturnValue(value.type_.clone()));
+        }
+        let converted = match value.expr_type {
+            ExprType::Integer(literal) => return_type.fits_literal(literal, &value.type_),
+            ExprType::Float(_) => return_type.is_float(),
+            _ => false,
+        };
+        if converted || value.type_.promote(return_type).as_ref() == Some(return_type) {
+            Ok(())
+        } else {
+            Err(Error::MismatchedTypes(
+                return_type.clone(),
+                value.type_.clone(),
+            ))
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: &AuroraType, right: &AuroraType) -> Result<()> {
+    if op.is_logical() {
+        return match [left, right]
+            .into_iter()
+            .find(|type_| **type_ != AuroraType::Bool)
+        {
+            Some(type_) => Err(Error::MismatchedTypes(AuroraType::Bool, type_.clone())),
+            None => Ok(()),
+        };
+    }
+    if op.is_shift() {
+        if !left.is_integer() || !right.is_integer() {
+            return Err(Error::Unexpected("bitwise operation on this type"));
+        }
+        return Ok(());
+    }
+    let Some(type_) = left.promote(right) else {
+        return Err(Error::MismatchedTypes(left.clone(), right.clone()));
+    };
+    // Strings, arrays and structs are held by address, which comparing them would compare
+    // instead of their contents
+    let valid = match op {
+        _ if op.is_ordering() => {
+            type_.is_integer() || type_.is_float() || matches!(type_, AuroraType::Enum(..))
+        }
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            type_.is_integer()
+                || type_.is_float()
+                || matches!(
+                    type_,
+                    AuroraType::Bool | AuroraType::Enum(..) | AuroraType::Ptr(_)
+                )
+        }
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => type_.is_integer(),
+        BinaryOp::Modulo => type_.is_integer(),
+        _ => type_.is_integer() || type_.is_float(),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Unexpected("operand types of this operator"))
+    }
+}
@@ -0,0 +1,103 @@
+// Helpers shared by the integration tests, not every test file uses all of them
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{self, Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use aurora_compiler::{compile, options::CompileOptions};
+
+// Message of the error compiling a source gives, with the place it was found at
+pub fn error(source: &str) -> String {
+    match compile(source.as_bytes(), CompileOptions::default()) {
+        Ok(_) => panic!("expected an error compiling:\n{}", source),
+        Err(error) => format!("{:?}", error),
+    }
+}
+
+// Compiles a source, panicking with its errors when it doesn't
+pub fn compiles(source: &str) {
+    if let Err(error) = compile(source.as_bytes(), CompileOptions::default()) {
+        panic!("{:?}\ncompiling:\n{}", error, source);
+    }
+}
+
+// A directory of its own for each call, tests running in parallel
+pub fn temp_dir() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+    let dir = env::temp_dir().join(format!("aurora-tests-{}-{}", process::id(), count));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Writes a source to a file of a new directory, returning its path
+pub fn source_file(source: &str) -> PathBuf {
+    let path = temp_dir().join("main.au");
+    fs::write(&path, source).unwrap();
+    path
+}
+
+// Runs the compiler's binary with the arguments
+pub fn driver(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aurora-compiler"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// Runs the compiler on a source with the arguments, the path of the source given last
+pub fn driver_on(source: &str, args: &[&str]) -> Output {
+    let path = source_file(source);
+    let mut args = args.to_vec();
+    args.push(path.to_str().unwrap());
+    driver(&args)
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+// The IR of every function of a source, as printed by `--emit=ir`
+pub fn ir(source: &str, args: &[&str]) -> String {
+    let mut args = args.to_vec();
+    args.push("--emit=ir");
+    let output = driver_on(source, &args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output)
+}
+
+// IR of the last function defined by a source, leaving out the ones the compiler adds
+pub fn last_function_ir(source: &str, args: &[&str]) -> String {
+    let ir = ir(source, args);
+    let start = ir.rfind("function ").unwrap();
+    ir[start..].to_string()
+}
+
+// Builds an executable from a source with the arguments, returning its path along with the
+// compiler's output
+pub fn build(source: &str, args: &[&str]) -> (PathBuf, Output) {
+    let path = source_file(source);
+    let executable = path.with_extension("exe");
+    let mut args = args.to_vec();
+    args.extend([path.to_str().unwrap(), "-o", executable.to_str().unwrap()]);
+    let output = driver(&args);
+    (executable, output)
+}
+
+// Builds an executable from a source and runs it, returning what it printed
+pub fn run(source: &str) -> String {
+    let (executable, output) = build(source, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = Command::new(&executable).output().unwrap();
+    assert!(output.status.success(), "{:?}", output.status);
+    stdout(&output)
+}
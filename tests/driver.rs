@@ -0,0 +1,510 @@
+mod common;
+
+use std::{
+    env,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use aurora_compiler::{link::Linker, options::Options};
+use common::{build, driver, source_file, stderr, stdout};
+
+fn options(args: &[&str]) -> Options {
+    Options::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+}
+
+#[test]
+fn linker_and_its_arguments_are_configurable() {
+    let options = options(&[
+        "--linker",
+        "clang",
+        "-L./lib",
+        "-l",
+        "foo",
+        "--link-arg=-static",
+        "--runtime",
+        "rt.c",
+        "main.au",
+    ]);
+    let command = Linker::new(&options).command("main.o", Path::new("aurora_runtime.c"));
+    assert_eq!(command.get_program(), "clang");
+    let args: Vec<_> = command
+        .get_args()
+        .map(|arg| arg.to_str().unwrap())
+        .collect();
+    assert_eq!(
+        args,
+        [
+            "rt.c",
+            "aurora_runtime.c",
+            "main.o",
+            "-lm",
+            "-L./lib",
+            "-lfoo",
+            "-static",
+            "-o",
+            "./build/test.exe"
+        ]
+    );
+}
+
+#[test]
+fn custom_linker_is_spawned() {
+    let (_, output) = build("putint(5);", &["--linker", "aurora-missing-linker"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("aurora-missing-linker"));
+}
+
+#[test]
+fn builds_outside_the_repository_without_text_relocations() {
+    let path = source_file("putint(5);");
+    let output = Command::new(env!("CARGO_BIN_EXE_aurora-compiler"))
+        .current_dir(path.parent().unwrap())
+        .args(["main.au", "-o", "main.exe"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("TEXTREL"), "{}", stderr(&output));
+    let output = Command::new(path.with_file_name("main.exe"))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "5\n");
+}
+
+#[test]
+fn symbols_show_their_linkage() {
+    let source = "extern sin(x: f64) f64;\nfn f() f64 { return sin(1.0); }";
+    let (_, output) = build(source, &["--print-symbols", "--emit=obj"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let symbols = stdout(&output);
+    assert!(
+        symbols.contains("Import   sin (1 params) -> f64"),
+        "{}",
+        symbols
+    );
+    assert!(
+        symbols.contains("Export   f (0 params) -> f64"),
+        "{}",
+        symbols
+    );
+    // By name, not in the order they were declared
+    let f = symbols.find("Export   f ").unwrap();
+    assert!(f < symbols.find("Import   sin ").unwrap(), "{}", symbols);
+}
+
+#[test]
+fn unused_variables_are_reported() {
+    let source = "fn f() i64 { let unused = 1; let used = 2; return used; }";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let warnings = stderr(&output);
+    assert!(
+        warnings.contains("Warning: unused variable `unused`"),
+        "{}",
+        warnings
+    );
+    assert!(!warnings.contains("`used`"), "{}", warnings);
+
+    let (_, output) = build(source, &["--emit=obj", "--no-warn-unused"]);
+    assert!(!stderr(&output).contains("unused"));
+}
+
+#[test]
+fn version_is_printed() {
+    let output = driver(&["--version"]);
+    assert!(output.status.success());
+    let version = stdout(&output);
+    assert!(
+        version.contains(concat!("aurora-compiler ", env!("CARGO_PKG_VERSION"))),
+        "{}",
+        version
+    );
+}
+
+#[test]
+fn bad_character_fails_the_build() {
+    let (_, output) = build("fn f() i64 { return 1 $ 2; }", &["--emit=obj"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("unknown char `$`"));
+}
+
+#[test]
+fn bad_character_between_items_does_not_hang() {
+    let path = source_file("@ fn main() i64 { return 0; }");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_aurora-compiler"))
+        .args([path.to_str().unwrap(), "--emit=obj"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("the compiler didn't stop");
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    assert!(!status.success());
+}
+
+#[test]
+fn statements_after_a_return_are_reported() {
+    let source = "fn f() i64 { return 1; putint(2); }";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let warnings = stderr(&output);
+    assert!(
+        warnings.contains("Warning: unreachable statement"),
+        "{}",
+        warnings
+    );
+}
+
+#[test]
+fn executables_need_an_entry() {
+    let (_, output) = build("fn f() i64 { return 1; }", &["--color=never"]);
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("no function `main`"), "{}", message);
+
+    let (_, output) = build("fn f() i64 { return 1; }", &["--emit=obj"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn custom_entry() {
+    let (executable, output) = build(
+        "fn start() i32 { putint(4); return 0; }",
+        &["--entry", "start"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = Command::new(&executable).output().unwrap();
+    assert_eq!(stdout(&output), "4\n");
+
+    let source = "fn start() i32 { return 0; } fn main() i32 { return 0; }";
+    let (_, output) = build(source, &["--entry", "start"]);
+    assert!(!output.status.success());
+
+    let (_, output) = build(source, &["--emit=obj", "--entry", "nope", "--color=never"]);
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(
+        message.contains("invalid value `nope` for `--entry`"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn pass_times_are_printed() {
+    let source = "fn main() i32 { putint(6); return 0; }";
+    let (executable, output) = build(source, &["--time-passes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let times = stderr(&output);
+    for pass in ["parsing", "type checking", "codegen", "emission", "linking"] {
+        assert!(times.contains(pass), "{}", times);
+    }
+    let output = Command::new(&executable).output().unwrap();
+    assert_eq!(stdout(&output), "6\n");
+
+    let (_, output) = build(source, &[]);
+    assert!(!stderr(&output).contains("codegen"));
+}
+
+// Values the REPL printed for lines piped to it, leaving out its prompts
+fn repl(lines: &str) -> Vec<String> {
+    let (output, _) = repl_output(lines);
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output)
+        .split(|char: char| char == '>' || char.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Pipes lines to the REPL, returning its output and the directory it built programs in
+fn repl_output(lines: &str) -> (Output, PathBuf) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_aurora-compiler"))
+        .args(["--repl", "--color=never"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let directory = env::temp_dir().join(format!("aurora-repl-{}", child.id()));
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(lines.as_bytes())
+        .unwrap();
+    (child.wait_with_output().unwrap(), directory)
+}
+
+#[test]
+fn repl_evaluates_piped_lines() {
+    let lines = "fn double(x: i64) i64 { return x * 2; }\n1 + 2\ndouble(21)\nlet y = 3\ny + 1\n";
+    assert_eq!(repl(lines), ["3", "42", "4"]);
+}
+
+#[test]
+fn repl_prints_the_output_of_a_let_once() {
+    let lines = "fn side() i64 { putint(7); return 1; }\nlet a = side()\na + 1\na + 2\n";
+    assert_eq!(repl(lines), ["7", "2", "3"]);
+}
+
+#[test]
+fn repl_keeps_every_statement() {
+    let lines = "let mut x = 1\nx = 5\nx\nwhile x < 8 { x = x + 1; }\nx\n";
+    assert_eq!(repl(lines), ["5", "8"]);
+}
+
+#[test]
+fn repl_reports_failing_programs_and_cleans_up() {
+    let (output, directory) = repl_output("assert(1 == 2)\n1 + 1\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("the program failed"),
+        "{}",
+        stderr(&output)
+    );
+    // The failed line isn't kept
+    assert!(stdout(&output).contains('2'), "{}", stdout(&output));
+    assert!(!directory.exists());
+}
+
+#[test]
+fn variables_named_after_functions_are_reported() {
+    let source = "fn foo() {} fn f() i64 { let foo = 1; return foo; }";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let warnings = stderr(&output);
+    assert!(
+        warnings.contains("Warning: variable `foo` has the name of a function"),
+        "{}",
+        warnings
+    );
+}
+
+#[test]
+fn missing_input_names_its_path() {
+    let output = driver(&["does/not/exist.au", "--color=never"]);
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains("does/not/exist.au"), "{}", message);
+
+    let path = source_file("fn f() {}");
+    let object = path.with_file_name("missing").join("main.o");
+    let output = driver(&[
+        path.to_str().unwrap(),
+        "--emit=obj",
+        "-o",
+        object.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    let message = stderr(&output);
+    assert!(message.contains(object.to_str().unwrap()), "{}", message);
+}
+
+#[test]
+fn types_are_explained() {
+    let source = "fn f(a: i32, b: f64) f64 {
+        let x = 1;
+        let mut y: u8 = 2;
+        if a > 0 { let z = b * 2.0; return z; }
+        return b;
+    }";
+    let (_, output) = build(source, &["--emit=obj", "--explain-types"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "fn f(a: i32, b: f64) f64\n    let x: i64\n    let mut y: u8\n    let z: f64\n"
+    );
+}
+
+#[test]
+fn assembly_is_printed() {
+    let (_, output) = build(
+        "fn f(a: i64) i64 { return a * 3; }",
+        &["--emit=obj", "--emit-asm"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let assembly = stdout(&output);
+    assert!(assembly.starts_with("f:\n"), "{}", assembly);
+    assert!(assembly.contains("ret"), "{}", assembly);
+}
+
+#[test]
+fn pipeline_stops_at_each_stage() {
+    let source = "fn main() i32 { return 0; }";
+    let path = source_file(source);
+    let input = path.to_str().unwrap();
+    let object = path.with_extension("o");
+    let executable = path.with_extension("exe");
+    let run = |stage: &str, output: &Path| {
+        let args = [input, "--emit", stage, "-o", output.to_str().unwrap()];
+        let result = driver(&args);
+        assert!(result.status.success(), "{}", stderr(&result));
+        stdout(&result)
+    };
+
+    let tokens = run("tokens", &object);
+    assert!(
+        tokens.starts_with("1:1 fn\n1:4 identifier: main\n"),
+        "{}",
+        tokens
+    );
+    let ast = run("ast", &object);
+    assert!(ast.contains("function_name: \"main\""), "{}", ast);
+    let ir = run("ir", &object);
+    assert!(ir.contains("function u0:0() -> i32"), "{}", ir);
+    assert!(!object.exists());
+
+    assert_eq!(run("obj", &object), "");
+    assert!(object.exists());
+    assert!(!executable.exists());
+    run("exe", &executable);
+    assert!(Command::new(&executable).status().unwrap().success());
+}
+
+#[test]
+fn errors_stop_at_the_limit() {
+    let source: String = (0..30)
+        .map(|i| format!("fn f{}() i64 {{ return missing; }}\n", i))
+        .collect();
+    let count = |args: &[&str]| {
+        let (_, output) = build(&source, args);
+        assert!(!output.status.success());
+        let errors = stderr(&output);
+        let count = errors.matches("Error:").count();
+        (count, errors.contains("too many errors, aborting"))
+    };
+    assert_eq!(count(&["--color=never"]), (21, true));
+    assert_eq!(count(&["--color=never", "--max-errors", "3"]), (4, true));
+    assert_eq!(count(&["--color=never", "--max-errors", "0"]), (30, false));
+}
+
+#[test]
+fn failed_lets_report_a_single_error() {
+    for source in [
+        "let x: i32 = 1.5; putint(x);",
+        "fn f() { let x: i32 = 1.5; putint(x); }",
+        "fn f() { let x = missing; putint(x + 1); }",
+        "let x = missing;\nputint(x + 1);",
+    ] {
+        let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+        assert!(!output.status.success());
+        let errors = stderr(&output);
+        assert_eq!(errors.matches("Error:").count(), 1, "{}", errors);
+    }
+}
+
+#[test]
+fn warnings_fail_the_build_as_errors() {
+    let source = "fn f() i64 { let unused = 1; return 2; }";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    for flag in ["-Werror", "--warnings-as-errors"] {
+        let (_, output) = build(source, &["--emit=obj", "--color=never", flag]);
+        assert!(!output.status.success());
+        let errors = stderr(&output);
+        // Only the line is known, as for the warning
+        assert!(
+            errors.contains(":1 Error: unused variable `unused`"),
+            "{}",
+            errors
+        );
+    }
+
+    let (_, output) = build(source, &["--emit=obj", "--error-format=json", "-Werror"]);
+    let errors = stderr(&output);
+    assert!(errors.contains("\"line\":1,\"col\":null"), "{}", errors);
+}
+
+#[test]
+fn diagnostics_as_json() {
+    let path = source_file("fn f() i64 {\n    let x = 1;\n    return x + y;\n}\n");
+    let input = path.to_str().unwrap();
+    let output = driver(&[input, "--emit=obj", "--error-format=json"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "{{\"file\":\"{}\",\"line\":3,\"col\":16,\"severity\":\"error\",\"message\":\"undefined identifier y\"}}\n",
+            input
+        )
+    );
+
+    let path = source_file("fn f() i64 {\n    let u = 1;\n    return 2;\n}\n");
+    let input = path.to_str().unwrap();
+    let object = path.with_extension("o");
+    let output = driver(&[
+        input,
+        "--emit=obj",
+        "--error-format=json",
+        "-o",
+        object.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "{{\"file\":\"{}\",\"line\":2,\"col\":null,\"severity\":\"warning\",\"message\":\"unused variable `u`\"}}\n",
+            input
+        )
+    );
+}
+
+#[test]
+fn piped_diagnostics_have_no_colors() {
+    let source = "fn f() i64 { let unused = 1; return missing; }";
+    let (_, output) = build(source, &["--emit=obj"]);
+    assert!(!output.status.success());
+    let errors = stderr(&output);
+    assert!(errors.contains("Error:"), "{}", errors);
+    assert!(!errors.contains('\x1b'), "{:?}", errors);
+
+    let (_, output) = build(source, &["--emit=obj", "--color=always"]);
+    assert!(stderr(&output).contains("\x1b["));
+}
+
+#[test]
+fn errors_show_their_line_with_a_caret() {
+    let source = "fn f() i64 {\n    let x = 1;\n    return x + y;\n}\n";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    assert!(!output.status.success());
+    let errors = stderr(&output);
+    assert!(
+        errors.contains("   3 |     return x + y;\n     |                ^\n"),
+        "{}",
+        errors
+    );
+
+    let source = "fn f() i64 {\n\tlet x = 1;\n\treturn x + y;\n}\n";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    let errors = stderr(&output);
+    assert!(
+        errors.contains("   3 | \treturn x + y;\n     | \t           ^\n"),
+        "{:?}",
+        errors
+    );
+
+    // Errors found while parsing a statement point into it too
+    let source = "fn f() {\n    let x: bool = 1.5;\n    if x {}\n}\n";
+    let (_, output) = build(source, &["--emit=obj", "--color=never"]);
+    let errors = stderr(&output);
+    assert!(
+        errors.contains("   2 |     let x: bool = 1.5;\n     |                   ^\n"),
+        "{}",
+        errors
+    );
+    assert!(!errors.contains("undefined identifier"), "{}", errors);
+}
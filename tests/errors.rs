@@ -0,0 +1,406 @@
+mod common;
+
+use common::{compiles, error};
+
+#[test]
+fn conditions_must_be_bool() {
+    compiles("fn f(a: i64, b: i64) i64 { if a < b { return a; } return b; }");
+    let error = error("fn f(a: i64) i64 { if a { return 1; } return 2; }");
+    assert!(
+        error.contains("condition must be of type 'bool' but got 'i64'"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn comparisons_do_not_chain() {
+    let error = error("fn f(a: i64, b: i64, c: i64) bool { return a < b < c; }");
+    assert!(
+        error.contains("comparison operators cannot be chained"),
+        "{}",
+        error
+    );
+    compiles("fn f(a: i64, b: i64, c: i64) bool { return (a < b) && (b < c); }");
+}
+
+#[test]
+fn empty_blocks() {
+    compiles("fn f() {}");
+    let error = error("fn f() i64 {}");
+    assert!(
+        error.contains("missing return of a value of type 'i64'"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn division_by_a_literal_zero() {
+    for source in [
+        "fn f() i64 { return 5 / 0; }",
+        "fn f() i64 { return 5 % 0; }",
+    ] {
+        let error = error(source);
+        assert!(error.contains("division by zero"), "{}", error);
+    }
+    compiles("fn f(x: i64) i64 { return 5 / x; }");
+}
+
+// A string is held by its address, so `==` would compare where two equal strings are stored
+#[test]
+fn strings_are_not_compared() {
+    for source in [
+        r#"fn f() bool { return "\x41" == "A"; }"#,
+        r#"fn f() bool { return "A" < "B"; }"#,
+    ] {
+        let error = error(source);
+        assert!(
+            error.contains("operand types of this operator"),
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn only_mutable_bindings_are_reassigned() {
+    compiles("fn f() i64 { let mut x = 1; x = 2; return x; }");
+    let error = error("fn f() i64 { let x = 1; x = 2; return x; }");
+    assert!(
+        error.contains("cannot assign twice to immutable variable `x`"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn every_error_of_a_function_is_reported() {
+    let error = error("fn f() i64 {\n let a: i64 = true;\n return b;\n}");
+    assert!(error.contains("mismatched type"), "{}", error);
+    assert!(error.contains("undefined identifier b"), "{}", error);
+}
+
+#[test]
+fn calls_to_a_function_that_failed_are_not_undefined() {
+    let error = error("fn f() i64 { return b; }\nfn g() i64 { return f(); }");
+    assert!(error.contains("undefined identifier b"), "{}", error);
+    assert!(!error.contains("undefined function"), "{}", error);
+}
+
+#[test]
+fn nested_calls_keep_their_types() {
+    compiles(
+        "fn g(x: i64) i64 { return x; }
+         fn f(x: i64) i64 { return x; }
+         fn h() i64 { return f(g(1)); }",
+    );
+    let error = error(
+        "fn g(x: i64) f64 { return 1.0; }
+         fn f(x: i64) i64 { return x; }
+         fn h() i64 { return f(g(1)); }",
+    );
+    assert!(
+        error.contains("was expecting 'i64' but got 'f64'"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn functions_can_be_called_before_their_definition() {
+    compiles("fn a() { b(); }\nfn b() {}");
+}
+
+#[test]
+fn deferred_initialisation_happens_once() {
+    for source in [
+        "fn f(c: bool) i64 { let x: i64; if c { x = 1; } else { x = 2; } x = 3; return x; }",
+        "fn f(n: i64) { let x: i64; let mut i = 0; while i < n { x = i; i = i + 1; } }",
+        "fn f() { let x: i64; loop { x = 1; break; } }",
+    ] {
+        let error = error(source);
+        assert!(error.contains("cannot assign twice"), "{}", error);
+    }
+    compiles("fn f(c: bool) i64 { let x: i64; if c { x = 1; return x; } x = 2; return x; }");
+}
+
+#[test]
+fn reading_before_assignment() {
+    for source in [
+        "fn f() { let x: i64; putint(x); }",
+        "fn f() { let mut x: i64; let y = x + 1; x = y; }",
+        "fn f(c: bool) { let x: i64; if c { x = 1; } putint(x); }",
+    ] {
+        let error = error(source);
+        assert!(
+            error.contains("variable `x` is read before it's assigned"),
+            "{}",
+            error
+        );
+    }
+    compiles("fn f(c: bool) { let x: i64; if c { x = 1; } else { return; } putint(x); }");
+    compiles("fn f() { let x: i64; let p = &x; *p = 1; putint(x); }");
+}
+
+#[test]
+fn literal_array_index_out_of_bounds() {
+    let error = error("fn f() { let mut a: [i64; 3]; a[3] = 1; }");
+    assert!(
+        error.contains("index 3 is out of bounds for an array of length 3"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn unknown_named_argument() {
+    let error = error(
+        "fn sub(a: i64, b: i64) i64 { return a - b; }\nfn f() i64 { return sub(a: 1, c: 2); }",
+    );
+    assert!(error.contains("no parameter named `c`"), "{}", error);
+}
+
+#[test]
+fn returned_values_match_the_prototype() {
+    compiles("fn f(x: i64) i64 { return x; }");
+    compiles("fn f() i32 { return 1; }");
+    let error = error("fn f() i64 { return 1.5; }");
+    assert!(
+        error.contains("was expecting 'i64' but got 'f64'"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn returns_with_and_without_a_value() {
+    let message = error("fn f() i64 { return; }");
+    assert!(
+        message.contains("`return` without a value in a function returning 'i64'"),
+        "{}",
+        message
+    );
+    let message = error("fn f() { return 1; }");
+    assert!(
+        message.contains("cannot return a value of type 'i64' from a function returning 'void'"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn variants_of_different_enums_do_not_compare() {
+    let message = error(
+        "enum Color { Red, Green }
+         enum Size { Small, Large }
+         fn f() bool { return Color.Green == Size.Small; }",
+    );
+    assert!(message.contains("mismatched type"), "{}", message);
+}
+
+#[test]
+fn aurora_calls_take_exact_argument_types() {
+    let message =
+        error("fn f(x: i64) i64 { return x; } fn main() i32 { let n: i32 = 1; f(n); return 0; }");
+    assert!(
+        message.contains("expecting 'i64' but got 'i32'"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn bitwise_operators_take_integers() {
+    for source in [
+        "fn f(a: f64, b: f64) f64 { return a & b; }",
+        "fn f(a: bool, b: bool) bool { return a | b; }",
+        "fn f(a: f64) f64 { return a << 1.0; }",
+    ] {
+        error(source);
+    }
+}
+
+#[test]
+fn bitwise_not_takes_integers() {
+    error("fn f() f64 { return ~1.0; }");
+}
+
+#[test]
+fn top_level_statements_with_an_explicit_main() {
+    let message = error("putint(5); fn main() i32 { return 0; }");
+    assert!(message.contains("explicit `fn main`"), "{}", message);
+}
+
+#[test]
+fn assert_takes_a_bool() {
+    error("fn f() { assert(1); }");
+}
+
+#[test]
+fn booleans_are_not_ordered() {
+    error("fn f() bool { return true < false; }");
+    error("fn f(a: bool, b: bool) bool { return a >= b; }");
+}
+
+#[test]
+fn void_calls_have_no_value() {
+    let source = "fn printnothing() {} fn f() { let x = printnothing(); }";
+    let message = error(source);
+    assert!(
+        message.contains("`printnothing` returns nothing"),
+        "{}",
+        message
+    );
+    let message = error("fn printnothing() {} fn f() i64 { return 1 + printnothing(); }");
+    assert!(message.contains("`printnothing`"), "{}", message);
+}
+
+#[test]
+fn literals_narrowing_to_several_overloads() {
+    let error = error("fn f(a: i32) {} fn f(a: u8) {} fn g() { f(1); }");
+    assert!(
+        error.contains("more than one overload of `f` takes (i64)"),
+        "{}",
+        error
+    );
+    compiles("fn f(a: i32) {} fn f(a: u8) {} fn g() { f(300); }");
+    compiles("fn f(a: i64) {} fn f(a: u8) {} fn g() { f(1); }");
+}
+
+#[test]
+fn literals_fit_their_annotated_type() {
+    compiles("fn f() { let a: i8 = 127; let b: i8 = -128; let c: u16 = 65535; let d: u16 = 0; }");
+    for (source, expected) in [
+        (
+            "fn f() { let x: i8 = 1000; }",
+            "literal `1000` is out of range for type 'i8'",
+        ),
+        (
+            "fn f() { let x: i8 = -129; }",
+            "literal `-129` is out of range for type 'i8'",
+        ),
+        (
+            "fn f() { let x: u16 = 65536; }",
+            "literal `65536` is out of range for type 'u16'",
+        ),
+        (
+            "fn f() { let x: u16 = -1; }",
+            "literal `-1` is out of range for type 'u16'",
+        ),
+        (
+            "fn f() { let x: i128 = 170141183460469231731687303715884105728; }",
+            "literal `170141183460469231731687303715884105728` is out of range for type 'i128'",
+        ),
+        (
+            "fn f() { let x = -170141183460469231731687303715884105729; }",
+            "literal `-170141183460469231731687303715884105729` is out of range for type 'i128'",
+        ),
+    ] {
+        let message = error(source);
+        assert!(message.contains(expected), "{}", message);
+    }
+}
+
+#[test]
+fn overflowing_literals_have_a_location() {
+    let message = error("fn f() i64 {\n    return 999999999999999999999999999999999999999999;\n}");
+    assert!(message.contains("<memory>:2:12"), "{}", message);
+    assert!(message.contains("doesn't fit in 128 bits"), "{}", message);
+
+    let message = error("fn f() i64 {\n    return 99999999999999999999i64;\n}");
+    assert!(message.contains("<memory>:2:12"), "{}", message);
+    assert!(
+        message.contains("out of range for type 'i64'"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn unknown_loop_label() {
+    let message = error("fn f() { 'a: loop { break 'b; } }");
+    assert!(message.contains("'b"), "{}", message);
+}
+
+#[test]
+fn undefined_variables_are_located() {
+    let message = error("fn f() i64 {\n    let a = 1;\n    return a + missing;\n}");
+    assert!(message.contains("<memory>:3:16"), "{}", message);
+    assert!(message.contains("missing"), "{}", message);
+}
+
+#[test]
+fn calls_are_checked_against_the_prototype() {
+    let prototype = "fn f(a: i64, b: f64) bool { return b > 0.0; }";
+    compiles(&format!(
+        "{} fn g() bool {{ return f(1, 2.0); }}",
+        prototype
+    ));
+    for call in [
+        "fn g() bool { return f(1.0, 2.0); }",
+        "fn g() i64 { return f(1, 2.0); }",
+    ] {
+        error(&format!("{} {}", prototype, call));
+    }
+    let message = error(&format!("{} fn g() bool {{ return f(1); }}", prototype));
+    assert!(message.contains("wrong argument count"), "{}", message);
+}
+
+#[test]
+fn void_parameters() {
+    let message = error("fn f(x: void) {}");
+    assert!(
+        message.contains("parameter `x` can't be void"),
+        "{}",
+        message
+    );
+    error("extern g(x: void);");
+}
+
+#[test]
+fn invalid_casts_are_located() {
+    let message = error("fn f() {\n    let x = 1.5 as bool;\n    putint(1);\n}");
+    assert!(message.contains("<memory>:2:13"), "{}", message);
+    assert!(
+        message.contains("cannot convert a value of type 'f64' to 'bool'"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn literals_stored_through_an_index_fit_the_element() {
+    let message = error("fn f() { let mut a: [i8; 2]; a[0] = 128; }");
+    assert!(
+        message.contains("out of range for type 'i8'"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn unclosed_blocks_end_at_the_end_of_file() {
+    for source in [
+        "fn main() i32 { return 0;",
+        "fn main() i32 {\n    if true { return 1; }\n    let x = ",
+        "fn main() i32 {\n    let x = ;\n    while true {",
+    ] {
+        let message = error(source);
+        assert!(message.contains("unexpected end of file"), "{}", message);
+    }
+    let message = error("fn main() i32 { return 0;");
+    assert!(message.contains("<memory>:1:15"), "{}", message);
+}
+
+#[test]
+fn lexing_errors_do_not_hide_later_declarations() {
+    for source in [
+        "@ fn main() i32 { return b(); } fn b() i32 { return 1; }",
+        "@ fn main() i32 { let p = P { x: 1 }; return 0; } struct P { x: i64 }",
+    ] {
+        let message = error(source);
+        assert!(message.contains("unknown char `@`"), "{}", message);
+        assert!(!message.contains("undefined"), "{}", message);
+    }
+}
@@ -0,0 +1,448 @@
+use aurora_compiler::{
+    ast::{BinaryOp, ExprType, Function},
+    intern::Symbol,
+    typing, AuroraType, Expr, Item, Lexer, Parser, Span, Token,
+};
+
+// Statements of the body of the last function of a source
+fn body(source: &str) -> Vec<Expr> {
+    let items = Parser::from_str(source).parse_all().unwrap();
+    let Some(Item::Function(function)) = items.into_iter().last() else {
+        panic!("no function in:\n{}", source);
+    };
+    let ExprType::Block(statements) = function.body.expr_type else {
+        panic!("a body that isn't a block");
+    };
+    statements
+}
+
+#[test]
+fn tokens_display_as_written() {
+    assert_eq!(Token::Eof.to_string(), "<eof>");
+    assert_eq!(Token::Float(1.5).to_string(), "1.5");
+    assert_eq!(Token::Float(2.0).to_string(), "2.0");
+    assert_eq!(Token::Integer(42, None).to_string(), "42");
+    assert_eq!(
+        Token::Integer(5, Some("i32".to_string())).to_string(),
+        "5i32"
+    );
+}
+
+#[test]
+fn assignment_and_equality_differ() {
+    let statements = body("fn f() bool { let mut x = 0; x = 1; return x == 1; }");
+    assert!(matches!(statements[1].expr_type, ExprType::Assign(..)));
+    let ExprType::Return(Some(value)) = &statements[2].expr_type else {
+        panic!("{:?}", statements[2]);
+    };
+    assert!(matches!(
+        value.expr_type,
+        ExprType::Binary(BinaryOp::Equal, ..)
+    ));
+}
+
+#[test]
+fn integer_literal_suffixes() {
+    let statements = body("fn f() { let x = 5i32; }");
+    let ExprType::Let(_, _, Some(value)) = &statements[0].expr_type else {
+        panic!("{:?}", statements[0]);
+    };
+    assert_eq!(value.type_, AuroraType::I32);
+
+    let error = Parser::from_str("fn f() { let x = 300u8; }")
+        .parse_all()
+        .unwrap_err();
+    let error = format!("{:?}", error);
+    assert!(
+        error.contains("literal `300` is out of range for type 'u8'"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn bindings_end_with_their_block() {
+    let error = Parser::from_str("fn f() i64 { { let x = 1; } return x; }")
+        .parse_all()
+        .unwrap_err();
+    assert!(format!("{:?}", error).contains("undefined identifier x"));
+
+    // The inner `x` shadows the outer one only until its block ends
+    let statements = body("fn f() i64 { let x = 1; { let x = 2.0; } return x; }");
+    let ExprType::Return(Some(value)) = &statements[2].expr_type else {
+        panic!("{:?}", statements[2]);
+    };
+    assert_eq!(value.type_, AuroraType::I64);
+}
+
+#[test]
+fn lexing_and_parsing_strings() {
+    let mut lexer = Lexer::from_str("let x = 1;");
+    assert_eq!(lexer.next_token().unwrap(), Token::Let);
+    assert_eq!(
+        lexer.next_token().unwrap(),
+        Token::Identifier(Symbol::intern("x"))
+    );
+
+    let items = Parser::from_str("fn f() {}").parse_all().unwrap();
+    assert!(matches!(items[..], [Item::Function(_)]));
+}
+
+#[test]
+fn lexer_iterates_over_tokens() {
+    let tokens: Vec<Token> = Lexer::from_str("fn f() i64 { return 2; }")
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let identifier = |name| Token::Identifier(Symbol::intern(name));
+    assert_eq!(
+        tokens,
+        [
+            Token::Def,
+            identifier("f"),
+            Token::OpenParen,
+            Token::CloseParen,
+            identifier("i64"),
+            Token::OpenBracket,
+            Token::Return,
+            Token::Integer(2, None),
+            Token::SemiColon,
+            Token::CloseBracket,
+        ]
+    );
+}
+
+#[test]
+fn tokens_know_their_span() {
+    let mut lexer = Lexer::from_str("let a = 1;\nlet bcd = 2;");
+    let spanned = loop {
+        let spanned = lexer.next_spanned().unwrap();
+        if spanned.value == Token::Identifier(Symbol::intern("bcd")) {
+            break spanned;
+        }
+    };
+    assert_eq!(
+        spanned.span,
+        Span {
+            start_line: 2,
+            start_col: 5,
+            end_line: 2,
+            end_col: 8,
+        }
+    );
+}
+
+#[test]
+fn comments_next_to_tokens_and_at_the_end() {
+    let tokens: Vec<Token> = Lexer::from_str("x=3# three\n;#end")
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        tokens,
+        [
+            Token::Identifier(Symbol::intern("x")),
+            Token::Equal,
+            Token::Integer(3, None),
+            Token::SemiColon,
+        ]
+    );
+}
+
+#[test]
+fn else_if_nests() {
+    let statements = body(
+        "fn f(x: i64) i64 {
+            if x < 0 { return 0; } else if x < 10 { return 1; } else { return 2; }
+        }",
+    );
+    let ExprType::IfElse(_, _, Some(else_)) = &statements[0].expr_type else {
+        panic!("{:?}", statements[0]);
+    };
+    let ExprType::IfElse(_, _, Some(else_)) = &else_.expr_type else {
+        panic!("{:?}", else_);
+    };
+    assert!(matches!(else_.expr_type, ExprType::Block(_)));
+}
+
+#[test]
+fn binary_operands_are_promoted() {
+    let statements = body("fn f(a: i32, b: i64, c: f64) { let x = a + b; let y = b + c; }");
+    let types: Vec<_> = statements
+        .iter()
+        .map(|statement| match &statement.expr_type {
+            ExprType::Let(_, _, Some(value)) => value.type_.clone(),
+            _ => panic!("{:?}", statement),
+        })
+        .collect();
+    assert_eq!(types, [AuroraType::I64, AuroraType::F64]);
+}
+
+#[test]
+fn hexadecimal_literals() {
+    let tokens: Vec<Token> = Lexer::from_str("0xff 0x1p4 0x1.8p1")
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        tokens,
+        [
+            Token::Integer(255, None),
+            Token::Float(16.0),
+            Token::Float(3.0),
+        ]
+    );
+
+    let error = Lexer::from_str("0x").next().unwrap().unwrap_err();
+    let error = format!("{:?}", error);
+    assert!(error.contains("malformed number literal"), "{}", error);
+}
+
+#[test]
+fn floats_without_a_digit_on_one_side() {
+    let tokens: Vec<Token> = Lexer::from_str(".5 5.").collect::<Result<_, _>>().unwrap();
+    assert_eq!(tokens, [Token::Float(0.5), Token::Float(5.0)]);
+}
+
+// The last function of a source, parsed but not type checked
+fn function(source: &str) -> Function {
+    let items = Parser::from_str(source).parse_all().unwrap();
+    let Some(Item::Function(function)) = items.into_iter().last() else {
+        panic!("no function in:\n{}", source);
+    };
+    *function
+}
+
+#[test]
+fn typing_pass_without_codegen() {
+    typing::check(
+        &mut function("fn f(a: i64, b: i64) bool { return a + b < 3; }"),
+        |_| None,
+    )
+    .unwrap();
+
+    let source = "fn f(a: f64) f64 { if 1 { return ~a; } return a; }";
+    let error = typing::check(&mut function(source), |_| None).unwrap_err();
+    let error = format!("{:?}", error);
+    assert!(
+        error.contains("condition must be of type 'bool' but got 'i64'"),
+        "{}",
+        error
+    );
+    assert!(error.contains("bitwise"), "{}", error);
+}
+
+#[test]
+fn only_values_held_by_value_are_compared() {
+    for source in [
+        "fn f(a: str, b: str) bool { return a == b; }",
+        "fn f(a: str, b: str) bool { return a < b; }",
+        "struct P { x: i64 }\nfn f(a: P, b: P) bool { return a == b; }",
+        "fn f(a: [i64; 2], b: [i64; 2]) bool { return a != b; }",
+        "fn f(a: *i64, b: *i64) bool { return a < b; }",
+        "fn f(a: bool, b: bool) bool { return a >= b; }",
+    ] {
+        let error = typing::check(&mut function(source), |_| None).unwrap_err();
+        let error = format!("{:?}", error);
+        assert!(
+            error.contains("operand types of this operator"),
+            "{}",
+            error
+        );
+    }
+    for source in [
+        "fn f(a: *i64, b: *i64) bool { return a == b; }",
+        "fn f(a: bool, b: bool) bool { return a != b; }",
+        "fn f(a: u8, b: f32) bool { return a < b; }",
+    ] {
+        typing::check(&mut function(source), |_| None).unwrap();
+    }
+}
+
+#[test]
+fn typing_pass_checks_calls_against_their_signature() {
+    let items = Parser::from_str("fn g(a: i64, b: f64) f64 { return b; }")
+        .parse_all()
+        .unwrap();
+    let Some(Item::Function(g)) = items.into_iter().next() else {
+        panic!("no function");
+    };
+    let g = typing::Signature::new(&g.prototype, false);
+    let signatures = |name: Symbol| (name == "g").then_some(&g);
+
+    // Named arguments are put in the order of the parameters
+    let source = "fn g(a: i64, b: f64) f64 { return b; } fn f() f64 { return g(b: 1.5, a: 2); }";
+    let mut f = function(source);
+    typing::check(&mut f, signatures).unwrap();
+    let ExprType::Block(statements) = f.body.expr_type else {
+        panic!("a body that isn't a block");
+    };
+    let ExprType::Return(Some(call)) = &statements[0].expr_type else {
+        panic!("not a return: {:?}", statements[0]);
+    };
+    let ExprType::Call(_, args) = &call.expr_type else {
+        panic!("not a call: {:?}", call);
+    };
+    assert!(
+        matches!(args[0].value.expr_type, ExprType::Integer(2)),
+        "{:?}",
+        args
+    );
+
+    let source = "fn g(a: i64, b: f64) f64 { return b; } fn f() f64 { return g(1.5, 2); }";
+    let error = typing::check(&mut function(source), signatures).unwrap_err();
+    let error = format!("{:?}", error);
+    assert!(
+        error.contains("was expecting 'i64' but got 'f64'"),
+        "{}",
+        error
+    );
+
+    let source = "fn g(a: i64, b: f64) f64 { return b; } fn f() f64 { return g(2); }";
+    let error = typing::check(&mut function(source), signatures).unwrap_err();
+    assert!(format!("{:?}", error).contains("wrong argument count"));
+}
+
+#[test]
+fn every_operator_has_a_precedence() {
+    use BinaryOp::*;
+    let ops = [
+        Or,
+        And,
+        Equal,
+        NotEqual,
+        LessThan,
+        LessEqual,
+        GreaterThan,
+        GreaterEqual,
+        BitOr,
+        BitXor,
+        BitAnd,
+        ShiftLeft,
+        ShiftRight,
+        Plus,
+        Minus,
+        Times,
+        Divide,
+        Modulo,
+    ];
+    assert!(ops.iter().all(|op| op.precedence() > 0));
+    assert!(Or.precedence() < And.precedence());
+    assert!(And.precedence() < Equal.precedence());
+    assert!(Equal.precedence() < BitOr.precedence());
+    assert!(BitOr.precedence() < BitXor.precedence());
+    assert!(BitXor.precedence() < BitAnd.precedence());
+    assert!(BitAnd.precedence() < ShiftLeft.precedence());
+    assert!(ShiftLeft.precedence() < Plus.precedence());
+    assert!(Plus.precedence() < Times.precedence());
+
+    let statements = body("fn f(a: i64) bool { return 1 + a * 2 == 3 << 1; }");
+    let ExprType::Return(Some(value)) = &statements[0].expr_type else {
+        panic!("{:?}", statements[0]);
+    };
+    let ExprType::Binary(Equal, left, right) = &value.expr_type else {
+        panic!("{:?}", value);
+    };
+    assert!(matches!(left.expr_type, ExprType::Binary(Plus, ..)));
+    assert!(matches!(right.expr_type, ExprType::Binary(ShiftLeft, ..)));
+}
+
+#[test]
+fn string_escapes_and_raw_strings() {
+    let tokens: Vec<Token> = Lexer::from_str(r#" "\x41\0\r" "A" r"a\nb" "#)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        tokens,
+        [
+            Token::Str("A\0\r".to_string()),
+            Token::Str("A".to_string()),
+            Token::Str(r"a\nb".to_string()),
+        ]
+    );
+
+    let error = Lexer::from_str(r#""\q""#).next().unwrap().unwrap_err();
+    let error = format!("{:?}", error);
+    assert!(error.contains("invalid escape sequence `\\q`"), "{}", error);
+}
+
+#[test]
+fn strings_hold_utf8_text() {
+    let tokens: Vec<Token> = Lexer::from_str(r#" "héllo" r"ü" "\xc3\xa9" "#)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        tokens,
+        [
+            Token::Str("héllo".to_string()),
+            Token::Str("ü".to_string()),
+            Token::Str("é".to_string()),
+        ]
+    );
+
+    let error = Lexer::from_str(r#""\xff""#).next().unwrap().unwrap_err();
+    let error = format!("{:?}", error);
+    assert!(error.contains("not valid UTF-8"), "{}", error);
+}
+
+#[test]
+fn identical_identifiers_share_a_symbol() {
+    let tokens: Vec<Token> = Lexer::from_str("count other count")
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(tokens[0], tokens[2]);
+    assert_ne!(tokens[0], tokens[1]);
+    let Token::Identifier(symbol) = tokens[0] else {
+        panic!("{:?}", tokens[0]);
+    };
+    assert_eq!(symbol, Symbol::intern("count"));
+    assert_eq!(symbol.as_str(), "count");
+    assert_eq!(symbol.to_string(), "count");
+}
+
+#[test]
+fn malformed_inputs_are_errors() {
+    for source in [
+        "fn",
+        "fn f(",
+        "fn f(x: ) {}",
+        "fn f(x: i64,,) {}",
+        "fn f() { return 1 + ; }",
+        "fn f() { let = 2; }",
+        "fn f() { 0x; }",
+        "fn f() { 99999999999999999999999999999999999999999999; }",
+        "extern",
+        "struct S { x: }",
+        "fn f() { match 1 { 1 => {} } }",
+        ")",
+        "fn f() { \"unterminated }",
+        "fn f() {",
+        "fn f() { let x = 1;",
+    ] {
+        assert!(Parser::from_str(source).parse_all().is_err(), "{}", source);
+    }
+}
+
+// What a tool like a linter would do, reading a source without compiling it
+#[test]
+fn tooling_reads_sources_through_the_public_api() {
+    let source = "extern puts(s: str) i32;\nstruct P { x: i64 }\nfn main() i32 { return 0; }";
+    let mut lexer = Lexer::new(source.as_bytes());
+    let mut lines = vec![];
+    while lexer.peek(0).unwrap() != &Token::Eof {
+        lines.push(lexer.next_spanned().unwrap().span.start_line);
+    }
+    assert_eq!(lines.first(), Some(&1));
+    assert_eq!(lines.last(), Some(&3));
+
+    let items = Parser::new(Lexer::new(source.as_bytes()))
+        .parse_all()
+        .unwrap();
+    let names: Vec<String> = items
+        .iter()
+        .map(|item| match item {
+            Item::Extern(prototypes) => prototypes[0].function_name.to_string(),
+            Item::Type(type_) => type_.to_string(),
+            Item::Function(function) => function.prototype.function_name.to_string(),
+            Item::Import(path) => path.clone(),
+        })
+        .collect();
+    assert_eq!(names, ["puts", "P", "main"]);
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::last_function_ir;
+
+#[test]
+fn literal_arithmetic_is_folded() {
+    let ir = last_function_ir("fn f() i64 { return 2 + 3 * 4; }", &[]);
+    assert!(ir.contains("iconst.i64 14"), "{}", ir);
+    assert!(!ir.contains("imul") && !ir.contains("iadd"), "{}", ir);
+}
+
+#[test]
+fn overflow_traps_only_when_asked() {
+    let source = "fn f(a: i64, b: i64) i64 { return a * b + a - b; }";
+    let ir = last_function_ir(source, &["--overflow=trap"]);
+    for instruction in ["smul_overflow", "sadd_overflow", "ssub_overflow", "trapnz"] {
+        assert!(ir.contains(instruction), "{}", ir);
+    }
+    let ir = last_function_ir(source, &["--overflow=wrap"]);
+    assert!(!ir.contains("overflow") && !ir.contains("trap"), "{}", ir);
+}
+
+#[test]
+fn i128_addition() {
+    let ir = last_function_ir("fn f(a: i128, b: i128) i128 { return a + b; }", &[]);
+    assert!(ir.contains("(i128, i128) -> i128"), "{}", ir);
+    assert!(ir.contains("iadd"), "{}", ir);
+}
+
+#[test]
+fn self_tail_calls_jump_back() {
+    let source = "fn count(n: i64, acc: i64) i64 {
+        if n == 0 { return acc; }
+        return count(n - 1, acc + 1);
+    }";
+    let ir = last_function_ir(source, &[]);
+    assert!(!ir.contains("call"), "{}", ir);
+    assert!(ir.contains("jump block1("), "{}", ir);
+
+    // Arguments are taken as a call takes them, named ones included
+    let source = "fn count(n: i32, acc: u8) u8 {
+        if n == 0i32 { return acc; }
+        return count(acc: acc + 1u8, n: n - 1i32);
+    }";
+    let ir = last_function_ir(source, &[]);
+    assert!(!ir.contains("call"), "{}", ir);
+}
+
+#[test]
+fn immutable_literals_are_propagated() {
+    let ir = last_function_ir("fn f() i64 { let x = 4; return x * 2; }", &[]);
+    assert!(ir.contains("iconst.i64 8"), "{}", ir);
+    assert!(!ir.contains("imul"), "{}", ir);
+
+    let ir = last_function_ir("fn f() i64 { let mut x = 4; x = 5; return x * 2; }", &[]);
+    assert!(ir.contains("imul"), "{}", ir);
+}
+
+#[test]
+fn bitwise_operators() {
+    for (operator, instruction) in [
+        ("&", "band"),
+        ("|", "bor"),
+        ("^", "bxor"),
+        ("<<", "ishl"),
+        (">>", "sshr"),
+    ] {
+        let source = format!("fn f(a: i64, b: i64) i64 {{ return a {} b; }}", operator);
+        let ir = last_function_ir(&source, &[]);
+        assert!(ir.contains(instruction), "{}", ir);
+    }
+    let ir = last_function_ir("fn f(a: u64, b: u64) u64 { return a >> b; }", &[]);
+    assert!(ir.contains("ushr"), "{}", ir);
+}
+
+#[test]
+fn assert_traps() {
+    let ir = last_function_ir("fn f(a: i64) { assert(1 < a); }", &[]);
+    assert!(ir.contains("brif"), "{}", ir);
+    assert!(ir.contains("trap user1"), "{}", ir);
+
+    let ir = last_function_ir("fn f(a: i64) { assert(a > 0, \"positive\"); }", &[]);
+    assert!(ir.contains("call"), "{}", ir);
+}
+
+#[test]
+fn dense_match_arms_use_a_jump_table() {
+    let source = "fn f(x: i64) {
+        match x {
+            1 => { putint(1); },
+            2 => { putint(2); },
+            3 => { putint(3); },
+            4 => { putint(4); },
+            _ => {},
+        }
+    }";
+    let ir = last_function_ir(source, &[]);
+    assert!(ir.contains("br_table"), "{}", ir);
+}
+
+#[test]
+fn loops_jump_back_to_their_body() {
+    let source = "fn f() i64 {
+        let mut i = 0;
+        loop {
+            i = i + 1;
+            if i == 3 { break; }
+        }
+        return i;
+    }";
+    let ir = last_function_ir(source, &[]);
+    assert!(ir.contains("jump block1"), "{}", ir);
+    assert!(ir.contains("brif"), "{}", ir);
+}
+
+#[test]
+fn f32_literals_are_f32_constants() {
+    let ir = last_function_ir("fn f() f32 { let x: f32 = 1.5; return x; }", &[]);
+    assert!(ir.contains("f32const"), "{}", ir);
+    assert!(!ir.contains("f64const"), "{}", ir);
+}
+
+#[test]
+fn print_calls_a_runtime_function_per_type() {
+    let callee = |source: &str| {
+        let ir = last_function_ir(source, &[]);
+        let line = ir.lines().find(|line| line.contains("fn0 = ")).unwrap();
+        let signature = ir.lines().find(|line| line.contains("sig0 = ")).unwrap();
+        (line.trim().to_string(), signature.trim().to_string())
+    };
+    let (int, int_signature) = callee("fn f() { print(3); }");
+    let (float, float_signature) = callee("fn f() { print(3.0); }");
+    assert_ne!(int, float);
+    assert_eq!(int_signature, "sig0 = (i64) system_v");
+    assert_eq!(float_signature, "sig0 = (f64) system_v");
+}
+
+#[test]
+fn float_comparison_modes() {
+    let source = "fn f(a: f64, b: f64) bool { return a < b; }";
+    for (args, condition) in [
+        (&[][..], "fcmp lt"),
+        (&["--float-compare", "ordered"][..], "fcmp lt"),
+        (&["--float-compare", "unordered"][..], "fcmp ult"),
+    ] {
+        let ir = last_function_ir(source, args);
+        assert!(ir.contains(condition), "{}", ir);
+    }
+    let ir = last_function_ir(
+        "fn f(a: f64, b: f64) bool { return a <= b; }",
+        &["--float-compare", "unordered"],
+    );
+    assert!(ir.contains("fcmp ule"), "{}", ir);
+
+    // Folded comparisons of constants follow the mode too
+    for source in [
+        "fn f() bool { return 0.0 / 0.0 < 1.0; }",
+        "fn f() bool { let z = 0.0; return z / z < 1.0; }",
+    ] {
+        for (args, result) in [
+            (&[][..], "iconst.i8 0"),
+            (&["--float-compare", "unordered"][..], "iconst.i8 1"),
+        ] {
+            let ir = last_function_ir(source, args);
+            assert!(ir.contains(result), "{}", ir);
+        }
+    }
+}
+
+#[test]
+fn checked_division_traps_on_zero() {
+    let source = "fn f(a: i64, b: i64) i64 { return a / b + a % b; }";
+    let ir = last_function_ir(source, &["--checked-div"]);
+    let check = ir.find("trapz v1, user2").unwrap();
+    assert!(check < ir.find("sdiv").unwrap(), "{}", ir);
+    assert_eq!(ir.matches("trapz").count(), 2, "{}", ir);
+
+    let ir = last_function_ir(source, &[]);
+    assert!(!ir.contains("trapz"), "{}", ir);
+}
@@ -0,0 +1,29 @@
+use aurora_compiler::{compile, options::CompileOptions};
+
+#[test]
+fn compiles_a_source_in_memory() {
+    let source = "fn add(a: i64, b: i64) i64 { return a + b; }";
+    let object = compile(source.as_bytes(), CompileOptions::default()).unwrap();
+    assert!(object.starts_with(b"\x7fELF"));
+}
+
+// Section names are in the object's string table
+fn has_section(object: &[u8], name: &str) -> bool {
+    object
+        .windows(name.len())
+        .any(|window| window == name.as_bytes())
+}
+
+#[test]
+fn debug_info_has_a_line_table() {
+    let source = "fn f() i64 {\n    return 1;\n}";
+    let options = CompileOptions {
+        debug_info: true,
+        ..CompileOptions::default()
+    };
+    let object = compile(source.as_bytes(), options).unwrap();
+    assert!(has_section(&object, ".debug_line"));
+
+    let object = compile(source.as_bytes(), CompileOptions::default()).unwrap();
+    assert!(!has_section(&object, ".debug_line"));
+}
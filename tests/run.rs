@@ -0,0 +1,572 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{build, driver, run, source_file, stderr};
+
+#[test]
+fn runtime_print_functions_need_no_extern() {
+    let source = "fn main() i32 { putint(5); putfloatd(1.5); return 0; }";
+    assert_eq!(run(source), "5\n1.500000\n");
+}
+
+#[test]
+fn variadic_extern_printf() {
+    let source = r#"
+        extern printf(format: str, ...) i32;
+        fn main() i32 {
+            printf("%d\n", 42);
+            return 0;
+        }
+    "#;
+    assert_eq!(run(source), "42\n");
+}
+
+#[test]
+fn ordering_comparisons() {
+    let source = "
+        let a = 2;
+        let b = 1;
+        print(a > b);
+        print(b >= b);
+        print(a <= b);
+        let x = 2.5;
+        let y = 1.0;
+        print(x > y);
+        print(y >= x);
+        print(y <= y);
+    ";
+    assert_eq!(run(source), "true\ntrue\nfalse\ntrue\nfalse\ntrue\n");
+}
+
+#[test]
+fn deferred_initialisation() {
+    assert_eq!(run("let x; x = 3; putint(x);"), "3\n");
+    let source = "
+        let c = false;
+        let x: i64;
+        if c { x = 1; } else { x = 2; }
+        putint(x);
+    ";
+    assert_eq!(run(source), "2\n");
+}
+
+#[test]
+fn array_elements_are_stored_and_loaded() {
+    let source = "
+        let mut a: [i64; 3];
+        a[0] = 5;
+        a[2] = a[0] * 2;
+        let i = 2;
+        putint(a[i]);
+    ";
+    assert_eq!(run(source), "10\n");
+}
+
+#[test]
+fn wide_indices_are_checked_before_narrowing() {
+    let source = "
+        let mut a: [i64; 2];
+        a[1] = 2;
+        let i: i128 = 1;
+        putint(a[i]);
+    ";
+    assert_eq!(run(source), "2\n");
+
+    // Only the high half is out of bounds
+    let source = "
+        let mut a: [i64; 2];
+        a[1] = 2;
+        let mut i: i128 = 1;
+        i = i + 18446744073709551616;
+        putint(a[i]);
+    ";
+    let (executable, output) = build(source, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = Command::new(&executable).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn arrays_are_copied_when_bound_or_assigned() {
+    let source = "
+        let mut a: [i64; 2];
+        a[0] = 1;
+        let mut b = a;
+        b[0] = 9;
+        putint(a[0]);
+        let mut c: [i64; 2];
+        c = a;
+        c[0] = 7;
+        putint(a[0]);
+        putint(b[0]);
+    ";
+    assert_eq!(run(source), "1\n1\n9\n");
+}
+
+#[test]
+fn pointers_to_locals() {
+    let source = "
+        fn set(p: *i64, value: i64) { *p = value; }
+        fn main() i32 {
+            let mut x = 1;
+            let p = &x;
+            putint(*p);
+            set(p, 7);
+            putint(x);
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "1\n7\n");
+}
+
+#[test]
+fn literals_past_64_bits() {
+    let source = "
+        let b: i128 = 100000000000000000000;
+        let u: u64 = 18446744073709551615;
+        print(b - 1 == 99999999999999999999);
+        print(u == 18446744073709551615);
+        let big: u128 = 170141183460469231731687303715884105728;
+        let max = 0xffffffffffffffffffffffffffffffffu128;
+        let min: i128 = -170141183460469231731687303715884105728;
+        print(big > 1);
+        print(max == 340282366920938463463374607431768211455);
+        print(min < 0);
+    ";
+    assert_eq!(run(source), "true\ntrue\ntrue\ntrue\ntrue\n");
+}
+
+#[test]
+fn sizes_of_types() {
+    let source = "
+        print(sizeof(i32) == 4);
+        print(sizeof(f64) == 8);
+        putint(sizeof([i16; 3]));
+    ";
+    assert_eq!(run(source), "true\ntrue\n6\n");
+}
+
+#[test]
+fn named_arguments_are_reordered() {
+    let source = "
+        fn sub(a: i64, b: i64) i64 { return a - b; }
+        fn main() i32 {
+            putint(sub(b: 1, a: 10));
+            putint(sub(10, b: 3));
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "9\n7\n");
+}
+
+#[test]
+fn deep_tail_recursion() {
+    let source = "
+        fn count(n: i64, acc: i64) i64 {
+            if n == 0 { return acc; }
+            return count(n - 1, acc + 1);
+        }
+        putint(count(10000000, 0));
+    ";
+    assert_eq!(run(source), "10000000\n");
+}
+
+#[test]
+fn imported_files_share_their_functions() {
+    let path = source_file("import \"math.au\";\nputint(double(21));");
+    fs::write(
+        path.with_file_name("math.au"),
+        "fn double(x: i64) i64 { return x * 2; }",
+    )
+    .unwrap();
+    let executable = path.with_extension("exe");
+    let output = driver(&[path.to_str().unwrap(), "-o", executable.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = Command::new(executable).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+}
+
+#[test]
+fn overloads_resolve_by_parameter_types() {
+    let source = "
+        fn show(x: i64) { putint(x); }
+        fn show(x: f64) { putfloatd(x); }
+        fn main() i32 {
+            show(3);
+            show(2.5);
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "3\n2.500000\n");
+}
+
+#[test]
+fn literals_narrow_to_the_overload_taking_them() {
+    let source = "
+        fn add(a: i32, b: i32) i32 { return a + b; }
+        fn add(a: f64, b: f64) f64 { return a + b; }
+        putint(add(1, 2) as i64);
+        putfloatd(add(1.5, 2.0));
+    ";
+    assert_eq!(run(source), "3\n3.500000\n");
+}
+
+#[test]
+fn struct_fields_are_read_back() {
+    let source = "
+        struct P { x: i32, y: f64, z: u8 }
+        let p = P { x: 1, y: 2.5, z: 255 };
+        putint(p.x as i64);
+        putfloatd(p.y);
+        putint(p.z as i64);
+    ";
+    assert_eq!(run(source), "1\n2.500000\n255\n");
+}
+
+#[test]
+fn enum_variants() {
+    let source = "
+        enum Color { Red, Green, Blue }
+        let c = Color.Green;
+        print(c == Color.Green);
+        print(c == Color.Blue);
+    ";
+    assert_eq!(run(source), "true\nfalse\n");
+}
+
+#[test]
+fn extern_calls_widen_narrower_arguments() {
+    let source = r#"
+        extern labs(x: i64) i64;
+        extern printf(format: str, ...) i32;
+        fn main() i32 {
+            let n: i32 = -7;
+            let small: i8 = 3;
+            let half: f32 = 0.5;
+            printf("%ld %d %.1f\n", labs(n), small, half);
+            return 0;
+        }
+    "#;
+    assert_eq!(run(source), "7 3 0.5\n");
+}
+
+#[test]
+fn bitwise_not() {
+    assert_eq!(run("print(~0i32); print(~5);"), "-1\n-6\n");
+}
+
+#[test]
+fn top_level_statements_make_up_main() {
+    assert_eq!(run("putint(5);"), "5\n");
+}
+
+#[test]
+fn match_on_integers_and_enums() {
+    let source = "
+        fn name(x: i64) {
+            match x {
+                1 => { putint(10); },
+                2 => { putint(20); },
+                3 => { putint(30); },
+                _ => { putint(0); },
+            }
+        }
+        enum Color { Red, Green }
+        fn main() i32 {
+            name(1);
+            name(3);
+            name(7);
+            let v: i8 = -1;
+            match v { -1 => { putint(-1); }, _ => { putint(1); } }
+            match Color.Green { Color.Red => { putint(100); }, _ => { putint(200); } }
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "10\n30\n0\n-1\n200\n");
+}
+
+#[test]
+fn booleans_compare_for_equality() {
+    let source = "
+        let t = true;
+        let f = false;
+        print(t == f);
+        print(t != f);
+        print(f == false);
+    ";
+    assert_eq!(run(source), "false\ntrue\ntrue\n");
+}
+
+#[test]
+fn nested_blocks_inside_an_if() {
+    let source = "
+        fn f(c: bool) {
+            if c {
+                { putint(1); }
+                { { putint(2); } }
+            }
+            putint(3);
+        }
+        fn main() i32 { f(true); f(false); return 0; }
+    ";
+    assert_eq!(run(source), "1\n2\n3\n3\n");
+}
+
+#[test]
+fn loop_until_a_break() {
+    let source = "
+        let mut i = 0;
+        loop {
+            i = i + 1;
+            if i == 3 { break; }
+        }
+        putint(i);
+    ";
+    assert_eq!(run(source), "3\n");
+}
+
+#[test]
+fn negative_and_narrow_literals() {
+    let source = "
+        print(.5 + 5.);
+        print(2 - -5);
+        let n: i32 = -5;
+        print(n);
+        let b: i8 = -1;
+        print(b);
+        print(~0i16);
+    ";
+    assert_eq!(run(source), "5.500000\n7\n-5\n-1\n-1\n");
+}
+
+#[test]
+fn early_return_from_an_if() {
+    let source = "
+        fn f(c: bool) i64 {
+            if (c) { return 1; }
+            return 2;
+        }
+        fn main() i32 { putint(f(true)); putint(f(false)); return 0; }
+    ";
+    assert_eq!(run(source), "1\n2\n");
+}
+
+#[test]
+fn labeled_break_leaves_both_loops() {
+    let source = "
+        let mut i = 0;
+        let mut j = 0;
+        'outer: while i < 10 {
+            j = 0;
+            while j < 10 {
+                if i * 10 + j == 23 { break 'outer; }
+                j = j + 1;
+            }
+            i = i + 1;
+        }
+        putint(i);
+        putint(j);
+    ";
+    assert_eq!(run(source), "2\n3\n");
+}
+
+#[test]
+fn comparisons_are_returned_as_bool() {
+    let source = "
+        fn lt(a: i64, b: i64) bool { return a < b; }
+        fn main() i32 { print(lt(1, 2)); print(lt(2, 1)); return 0; }
+    ";
+    assert_eq!(run(source), "true\nfalse\n");
+}
+
+#[test]
+fn structs_are_returned_by_value() {
+    let source = "
+        struct Pair { a: i64, b: f64 }
+        fn make(a: i64) Pair { return Pair { a: a, b: 0.5 }; }
+        fn main() i32 {
+            let p = make(7);
+            putint(p.a);
+            putfloatd(p.b);
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "7\n0.500000\n");
+}
+
+#[test]
+fn final_expression_is_the_result() {
+    let source = "
+        fn f() i64 { 42 }
+        fn pick(c: bool) i64 { let x = 1; if c { return 2; } x + 10 }
+        fn main() i32 { putint(f()); putint(pick(true)); putint(pick(false)); return 0; }
+    ";
+    assert_eq!(run(source), "42\n2\n11\n");
+}
+
+#[test]
+fn float_literals_narrow_to_f32() {
+    let source = "
+        fn half(x: f32) f32 { let two: f32 = 2.0; return x / two; }
+        fn main() i32 {
+            let x: f32 = 1.5;
+            print(half(x));
+            print(half(3.0));
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "0.750000\n1.500000\n");
+}
+
+#[test]
+fn saturating_and_wrapping_intrinsics() {
+    let source = "
+        let a: i8 = 120;
+        let b: i8 = 10;
+        print(saturating_add(a, b));
+        print(wrapping_add(a, b));
+        print(saturating_sub(-a, b));
+        let c: u8 = 3;
+        print(saturating_sub(c, 5));
+        print(saturating_mul(a, b));
+    ";
+    assert_eq!(run(source), "127\n-126\n-128\n0\n127\n");
+}
+
+#[test]
+fn do_while_runs_its_body_once() {
+    let source = "
+        let mut i = 10;
+        do { putint(i); i = i + 1; } while (i < 3);
+        do { i = i - 1; } while (i > 7);
+        putint(i);
+    ";
+    assert_eq!(run(source), "10\n7\n");
+}
+
+#[test]
+fn extern_blocks_declare_several_functions() {
+    let source = r#"
+        extern {
+            fn puts(s: str) i32;
+            fn putchar(c: i32) i32;
+        }
+        fn main() i32 {
+            puts("hi");
+            putchar(65);
+            putchar(10);
+            return 0;
+        }
+    "#;
+    assert_eq!(run(source), "hi\nA\n");
+}
+
+#[test]
+fn comparisons_stored_in_bool_locals() {
+    let source = "
+        fn check(a: i64, b: f64) {
+            let small = a < 10;
+            let mut positive: bool = b > 0.0;
+            if small { putint(1); } else { putint(0); }
+            while positive { putint(2); positive = false; }
+            print(small == positive);
+        }
+        fn main() i32 { check(3, 1.0); check(30, -1.0); return 0; }
+    ";
+    assert_eq!(run(source), "1\n2\nfalse\n0\ntrue\n");
+}
+
+#[test]
+fn conversions_between_integers_and_bool() {
+    let source = "
+        print(5 as bool);
+        print(0 as bool);
+        putint(true as i64);
+        putint(false as i64);
+        let b = 2 > 1;
+        print(b as i32 + 1i32);
+    ";
+    assert_eq!(run(source), "true\nfalse\n1\n0\n2\n");
+}
+
+#[test]
+fn literals_stored_through_an_index_or_a_pointer() {
+    let source = "
+        let mut a: [u8; 2];
+        a[0] = 200;
+        let mut x: i16 = 0;
+        let p = &x;
+        *p = -300;
+        let mut f: [f32; 1];
+        f[0] = 1.5;
+        print(a[0]);
+        print(x);
+        print(f[0]);
+    ";
+    assert_eq!(run(source), "200\n-300\n1.500000\n");
+}
+
+#[test]
+fn extern_pointer_parameters() {
+    let source = r#"
+        extern strlen(s: *i8) i64;
+        extern fn puts(s: str) i32;
+        fn main() i32 {
+            let mut text: [i8; 4];
+            text[0] = 104;
+            text[1] = 105;
+            text[2] = 0;
+            putint(strlen(&text[0]));
+            puts("there");
+            return 0;
+        }
+    "#;
+    assert_eq!(run(source), "2\nthere\n");
+}
+
+#[test]
+fn equality_comparisons() {
+    let source = "
+        fn same(a: i64, b: i64) bool { return a == b; }
+        fn main() i32 {
+            print(same(4, 4));
+            print(same(4, 5));
+            print(2.5 == 2.5);
+            print(1 + 2 == 3);
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "true\nfalse\ntrue\ntrue\n");
+}
+
+#[test]
+fn return_from_inside_a_while() {
+    let source = "
+        fn first_over(limit: i64) i64 {
+            let mut i = 0;
+            while (i < 100) {
+                if (i * i > limit) { return i; }
+                i = i + 1;
+            }
+            return -1;
+        }
+        fn main() i32 {
+            putint(first_over(10));
+            putint(first_over(100000));
+            return 0;
+        }
+    ";
+    assert_eq!(run(source), "4\n-1\n");
+}
+
+#[test]
+fn non_ascii_strings_are_printed_as_written() {
+    let source = "
+        extern puts(s: str) i32;
+        puts(\"héllo\");
+        puts(\"\\xc3\\xa9t\\xc3\\xa9\");
+        puts(\"\\x41\");
+    ";
+    assert_eq!(run(source), "héllo\nété\nA\n");
+}